@@ -11,6 +11,8 @@ pub struct Model {
     pub guild_id: i64,
     pub channel_id: i64,
     pub timestamp: DateTimeWithTimeZone,
+    pub deleted_at: Option<DateTimeWithTimeZone>,
+    pub edited_count: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -2,5 +2,15 @@
 
 pub mod prelude;
 
+pub mod backfill_cursors;
+pub mod cookie_outbox;
+pub mod cookie_submissions;
+pub mod daily_channel_activity;
+pub mod daily_user_activity;
+pub mod digest_posts;
+pub mod known_channels;
+pub mod known_users;
+pub mod leaked_cookies;
 pub mod messages;
 pub mod pending_flushes;
+pub mod rollup_state;
@@ -1,3 +1,11 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.13
 
-pub use super::{messages::Entity as Messages, pending_flushes::Entity as PendingFlushes};
+pub use super::{
+    backfill_cursors::Entity as BackfillCursors, cookie_outbox::Entity as CookieOutbox,
+    cookie_submissions::Entity as CookieSubmissions,
+    daily_channel_activity::Entity as DailyChannelActivity,
+    daily_user_activity::Entity as DailyUserActivity, digest_posts::Entity as DigestPosts,
+    known_channels::Entity as KnownChannels, known_users::Entity as KnownUsers,
+    leaked_cookies::Entity as LeakedCookies, messages::Entity as Messages,
+    pending_flushes::Entity as PendingFlushes, rollup_state::Entity as RollupState,
+};
@@ -6,6 +6,21 @@ impl Messages {
     pub fn timestamp(&self) -> DateTime<Utc> {
         self.timestamp.into()
     }
+    pub fn message_id(&self) -> MessageId {
+        MessageId::new(self.message_id as u64)
+    }
+    pub fn user_id(&self) -> UserId {
+        UserId::new(self.user_id as u64)
+    }
+    pub fn guild_id(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+    pub fn channel_id(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+    pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at.map(Into::into)
+    }
 }
 
 use crate::pending_flushes::Model as PendingFlushes;
@@ -35,3 +50,95 @@ impl PendingFlushes {
         self.created_at.into()
     }
 }
+
+use crate::cookie_outbox::Model as CookieOutbox;
+impl CookieOutbox {
+    pub fn user_id(&self) -> UserId {
+        UserId::new(self.user_id as u64)
+    }
+    pub fn next_attempt_at(&self) -> DateTime<Utc> {
+        self.next_attempt_at.into()
+    }
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at.into()
+    }
+    pub fn guild_id(&self) -> Option<GuildId> {
+        self.guild_id.map(|id| GuildId::new(id as u64))
+    }
+}
+
+use crate::cookie_submissions::Model as CookieSubmissions;
+impl CookieSubmissions {
+    pub fn user_id(&self) -> UserId {
+        UserId::new(self.user_id as u64)
+    }
+    pub fn guild_id(&self) -> Option<GuildId> {
+        self.guild_id.map(|id| GuildId::new(id as u64))
+    }
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at.into()
+    }
+}
+
+use crate::backfill_cursors::Model as BackfillCursors;
+impl BackfillCursors {
+    pub fn channel_id(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+    pub fn oldest_message_id(&self) -> Option<MessageId> {
+        self.oldest_message_id.map(|id| MessageId::new(id as u64))
+    }
+    pub fn until(&self) -> DateTime<Utc> {
+        self.until.into()
+    }
+}
+
+use crate::known_users::Model as KnownUsers;
+impl KnownUsers {
+    pub fn user_id(&self) -> UserId {
+        UserId::new(self.user_id as u64)
+    }
+    /// The global display name, the username if none is set
+    pub fn display_name(&self) -> &str {
+        self.global_name.as_deref().unwrap_or(&self.name)
+    }
+    pub fn last_seen_at(&self) -> DateTime<Utc> {
+        self.last_seen_at.into()
+    }
+}
+
+use crate::known_channels::Model as KnownChannels;
+impl KnownChannels {
+    pub fn channel_id(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+    pub fn guild_id(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+    pub fn kind(&self) -> ChannelType {
+        ChannelType::from(self.kind as u8)
+    }
+    pub fn parent_id(&self) -> Option<ChannelId> {
+        self.parent_id.map(|id| ChannelId::new(id as u64))
+    }
+    pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at.map(Into::into)
+    }
+}
+
+use crate::leaked_cookies::Model as LeakedCookies;
+impl LeakedCookies {
+    pub fn user_id(&self) -> UserId {
+        UserId::new(self.user_id as u64)
+    }
+    pub fn guild_id(&self) -> Option<GuildId> {
+        self.guild_id.map(|id| GuildId::new(id as u64))
+    }
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at.into()
+    }
+    /// The cookies, one per line
+    pub fn cookies(&self) -> Vec<String> {
+        self.cookies.lines().map(str::to_owned).collect()
+    }
+}
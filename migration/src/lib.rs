@@ -3,6 +3,14 @@ pub use sea_orm_migration::prelude::*;
 mod m20220101_000001_create_table;
 mod m20250704_012322_add_flush_reason;
 mod m20250710_000001_optimize_channel_stats;
+mod m20261018_000001_create_cookie_outbox;
+mod m20261018_000002_create_cookie_submissions;
+mod m20261018_000003_create_daily_activity;
+mod m20261018_000004_create_backfill_cursors;
+mod m20261018_000005_track_deleted_messages;
+mod m20261018_000006_create_digest_posts;
+mod m20261018_000007_create_known_names;
+mod m20261018_000008_create_leaked_cookies;
 
 pub struct Migrator;
 
@@ -13,6 +21,14 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20250704_012322_add_flush_reason::Migration),
             Box::new(m20250710_000001_optimize_channel_stats::Migration),
+            Box::new(m20261018_000001_create_cookie_outbox::Migration),
+            Box::new(m20261018_000002_create_cookie_submissions::Migration),
+            Box::new(m20261018_000003_create_daily_activity::Migration),
+            Box::new(m20261018_000004_create_backfill_cursors::Migration),
+            Box::new(m20261018_000005_track_deleted_messages::Migration),
+            Box::new(m20261018_000006_create_digest_posts::Migration),
+            Box::new(m20261018_000007_create_known_names::Migration),
+            Box::new(m20261018_000008_create_leaked_cookies::Migration),
         ]
     }
 }
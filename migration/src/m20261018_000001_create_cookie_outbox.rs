@@ -0,0 +1,58 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Cookie submissions waiting to be retried while the cookie endpoint is down
+        manager
+            .create_table(
+                Table::create()
+                    .table(CookieOutbox::Table)
+                    .if_not_exists()
+                    .col(pk_auto(CookieOutbox::Id))
+                    .col(big_unsigned(CookieOutbox::UserId))
+                    .col(text_uniq(CookieOutbox::Cookie))
+                    .col(integer(CookieOutbox::Attempts).default(Expr::value(0)))
+                    .col(timestamp_with_time_zone(CookieOutbox::NextAttemptAt))
+                    .col(text_null(CookieOutbox::LastError))
+                    .col(
+                        timestamp_with_time_zone(CookieOutbox::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_cookie_outbox_next_attempt_at")
+                    .table(CookieOutbox::Table)
+                    .col(CookieOutbox::NextAttemptAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CookieOutbox::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CookieOutbox {
+    Table,
+    Id,
+    UserId,
+    Cookie,
+    Attempts,
+    NextAttemptAt,
+    LastError,
+    CreatedAt,
+}
@@ -0,0 +1,96 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Ledger of cookie contributions, only a hash of the cookie is kept
+        manager
+            .create_table(
+                Table::create()
+                    .table(CookieSubmissions::Table)
+                    .if_not_exists()
+                    .col(pk_auto(CookieSubmissions::Id))
+                    .col(big_unsigned(CookieSubmissions::UserId))
+                    .col(big_unsigned_null(CookieSubmissions::GuildId))
+                    .col(string_len(CookieSubmissions::CookieHash, 64))
+                    .col(string(CookieSubmissions::Status))
+                    .col(text_null(CookieSubmissions::Response))
+                    .col(
+                        timestamp_with_time_zone(CookieSubmissions::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_cookie_submissions_user_created_at")
+                    .table(CookieSubmissions::Table)
+                    .col(CookieSubmissions::UserId)
+                    .col(CookieSubmissions::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_cookie_submissions_guild_created_at")
+                    .table(CookieSubmissions::Table)
+                    .col(CookieSubmissions::GuildId)
+                    .col(CookieSubmissions::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Queued cookies remember where they were submitted so the ledger can attribute them
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CookieOutbox::Table)
+                    .add_column(big_unsigned_null(CookieOutbox::GuildId))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CookieOutbox::Table)
+                    .drop_column(CookieOutbox::GuildId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(CookieSubmissions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CookieSubmissions {
+    Table,
+    Id,
+    UserId,
+    GuildId,
+    CookieHash,
+    Status,
+    Response,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum CookieOutbox {
+    Table,
+    GuildId,
+}
@@ -0,0 +1,124 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Message counts per channel and UTC day
+        manager
+            .create_table(
+                Table::create()
+                    .table(DailyChannelActivity::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(DailyChannelActivity::GuildId))
+                    .col(date(DailyChannelActivity::Day))
+                    .col(big_unsigned(DailyChannelActivity::ChannelId))
+                    .col(big_integer(DailyChannelActivity::MessageCount).default(Expr::value(0)))
+                    .primary_key(
+                        Index::create()
+                            .col(DailyChannelActivity::GuildId)
+                            .col(DailyChannelActivity::Day)
+                            .col(DailyChannelActivity::ChannelId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Message counts per user, channel and UTC day, the channel is kept for channel filters
+        manager
+            .create_table(
+                Table::create()
+                    .table(DailyUserActivity::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(DailyUserActivity::GuildId))
+                    .col(date(DailyUserActivity::Day))
+                    .col(big_unsigned(DailyUserActivity::UserId))
+                    .col(big_unsigned(DailyUserActivity::ChannelId))
+                    .col(big_integer(DailyUserActivity::MessageCount).default(Expr::value(0)))
+                    .primary_key(
+                        Index::create()
+                            .col(DailyUserActivity::GuildId)
+                            .col(DailyUserActivity::Day)
+                            .col(DailyUserActivity::UserId)
+                            .col(DailyUserActivity::ChannelId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Last day whose rollups are complete
+        manager
+            .create_table(
+                Table::create()
+                    .table(RollupState::Table)
+                    .if_not_exists()
+                    .col(string(RollupState::Name).primary_key())
+                    .col(date(RollupState::RolledUpTo))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Backfill every day before today from the raw messages
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "INSERT OR REPLACE INTO daily_channel_activity (guild_id, day, channel_id, message_count)
+             SELECT guild_id, date(timestamp), channel_id, COUNT(*) FROM messages
+             WHERE date(timestamp) < date('now')
+             GROUP BY guild_id, date(timestamp), channel_id",
+        )
+        .await?;
+        db.execute_unprepared(
+            "INSERT OR REPLACE INTO daily_user_activity (guild_id, day, user_id, channel_id, message_count)
+             SELECT guild_id, date(timestamp), user_id, channel_id, COUNT(*) FROM messages
+             WHERE date(timestamp) < date('now')
+             GROUP BY guild_id, date(timestamp), user_id, channel_id",
+        )
+        .await?;
+        db.execute_unprepared(
+            "INSERT OR REPLACE INTO rollup_state (name, rolled_up_to)
+             VALUES ('messages', date('now', '-1 day'))",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RollupState::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(DailyUserActivity::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(DailyChannelActivity::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DailyChannelActivity {
+    Table,
+    GuildId,
+    Day,
+    ChannelId,
+    MessageCount,
+}
+
+#[derive(DeriveIden)]
+enum DailyUserActivity {
+    Table,
+    GuildId,
+    Day,
+    UserId,
+    ChannelId,
+    MessageCount,
+}
+
+#[derive(DeriveIden)]
+enum RollupState {
+    Table,
+    Name,
+    RolledUpTo,
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // How far back the history of each channel has been crawled
+        manager
+            .create_table(
+                Table::create()
+                    .table(BackfillCursors::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(BackfillCursors::ChannelId).primary_key())
+                    .col(big_unsigned(BackfillCursors::GuildId))
+                    .col(big_unsigned_null(BackfillCursors::OldestMessageId))
+                    .col(timestamp_with_time_zone(BackfillCursors::Until))
+                    .col(boolean(BackfillCursors::Done).default(false))
+                    .col(
+                        timestamp_with_time_zone(BackfillCursors::UpdatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BackfillCursors::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum BackfillCursors {
+    Table,
+    ChannelId,
+    GuildId,
+    OldestMessageId,
+    Until,
+    Done,
+    UpdatedAt,
+}
@@ -0,0 +1,103 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only takes one column per ALTER TABLE
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Messages::Table)
+                    .add_column(timestamp_with_time_zone_null(Messages::DeletedAt))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Messages::Table)
+                    .add_column(integer(Messages::EditedCount).default(Expr::value(0)))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Deleted messages stay in the rollups, counted apart so stats can leave them out
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DailyChannelActivity::Table)
+                    .add_column(
+                        big_integer(DailyChannelActivity::DeletedCount).default(Expr::value(0)),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DailyUserActivity::Table)
+                    .add_column(
+                        big_integer(DailyUserActivity::DeletedCount).default(Expr::value(0)),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DailyUserActivity::Table)
+                    .drop_column(DailyUserActivity::DeletedCount)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DailyChannelActivity::Table)
+                    .drop_column(DailyChannelActivity::DeletedCount)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Messages::Table)
+                    .drop_column(Messages::EditedCount)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Messages::Table)
+                    .drop_column(Messages::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Messages {
+    Table,
+    DeletedAt,
+    EditedCount,
+}
+
+#[derive(DeriveIden)]
+enum DailyChannelActivity {
+    Table,
+    DeletedCount,
+}
+
+#[derive(DeriveIden)]
+enum DailyUserActivity {
+    Table,
+    DeletedCount,
+}
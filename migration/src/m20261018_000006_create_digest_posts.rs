@@ -0,0 +1,39 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // End of the latest period whose activity digest was posted in each guild
+        manager
+            .create_table(
+                Table::create()
+                    .table(DigestPosts::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(DigestPosts::GuildId).primary_key())
+                    .col(timestamp_with_time_zone(DigestPosts::PeriodEnd))
+                    .col(
+                        timestamp_with_time_zone(DigestPosts::PostedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DigestPosts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DigestPosts {
+    Table,
+    GuildId,
+    PeriodEnd,
+    PostedAt,
+}
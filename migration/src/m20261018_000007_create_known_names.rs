@@ -0,0 +1,79 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Last known names of the users seen through the gateway
+        manager
+            .create_table(
+                Table::create()
+                    .table(KnownUsers::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(KnownUsers::UserId).primary_key())
+                    .col(string(KnownUsers::Name))
+                    .col(string_null(KnownUsers::GlobalName))
+                    .col(timestamp_with_time_zone(KnownUsers::LastSeenAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Last known names and places of the channels and threads, kept after they are deleted
+        manager
+            .create_table(
+                Table::create()
+                    .table(KnownChannels::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(KnownChannels::ChannelId).primary_key())
+                    .col(big_unsigned(KnownChannels::GuildId))
+                    .col(string(KnownChannels::Name))
+                    .col(integer(KnownChannels::Kind))
+                    .col(big_unsigned_null(KnownChannels::ParentId))
+                    .col(timestamp_with_time_zone_null(KnownChannels::DeletedAt))
+                    .col(timestamp_with_time_zone(KnownChannels::UpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_known_channels_guild")
+                    .table(KnownChannels::Table)
+                    .col(KnownChannels::GuildId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(KnownChannels::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(KnownUsers::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum KnownUsers {
+    Table,
+    UserId,
+    Name,
+    GlobalName,
+    LastSeenAt,
+}
+
+#[derive(DeriveIden)]
+enum KnownChannels {
+    Table,
+    ChannelId,
+    GuildId,
+    Name,
+    Kind,
+    ParentId,
+    DeletedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,43 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Cookies taken out of redacted messages, waiting for their authors to submit them from DM
+        manager
+            .create_table(
+                Table::create()
+                    .table(LeakedCookies::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(LeakedCookies::MessageId).primary_key())
+                    .col(big_unsigned(LeakedCookies::UserId))
+                    .col(big_unsigned_null(LeakedCookies::GuildId))
+                    .col(text(LeakedCookies::Cookies))
+                    .col(
+                        timestamp_with_time_zone(LeakedCookies::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LeakedCookies::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum LeakedCookies {
+    Table,
+    MessageId,
+    UserId,
+    GuildId,
+    Cookies,
+    CreatedAt,
+}
@@ -0,0 +1,285 @@
+//! Typed client for the clewdr cookie service.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use reqwest::{Client, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use snafu::{ResultExt, Snafu};
+
+use crate::config::BotCfg;
+
+#[derive(Snafu, Debug)]
+#[snafu(visibility(pub(crate)))]
+pub enum ClewdrError {
+    #[snafu(display("Invalid clewdr endpoint: {source}"))]
+    Endpoint { source: url::ParseError },
+    #[snafu(display("Clewdr is unreachable: {source}"))]
+    Unreachable { source: reqwest::Error },
+    #[snafu(display("Clewdr rejected the admin secret"))]
+    Unauthorized,
+    #[snafu(display("Cookie not found in clewdr"))]
+    NotFound,
+    #[snafu(display("Clewdr returned {status}: {message}"))]
+    Status { status: StatusCode, message: String },
+    #[snafu(display("Unexpected response from clewdr: {source}"))]
+    Decode { source: reqwest::Error },
+}
+
+impl ClewdrError {
+    /// HTTP status returned by clewdr, if it answered at all.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ClewdrError::Unauthorized => Some(StatusCode::UNAUTHORIZED),
+            ClewdrError::NotFound => Some(StatusCode::NOT_FOUND),
+            ClewdrError::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether the request may succeed later, i.e. clewdr is down or overloaded.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ClewdrError::Unreachable { .. } => true,
+            ClewdrError::Status { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether clewdr turned the cookie itself down, so submitting it again cannot help.
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self.status(),
+            Some(StatusCode::CONFLICT | StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY)
+        )
+    }
+}
+
+/// A usable or exhausted cookie in the pool.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PooledCookie {
+    pub cookie: String,
+    /// Unix timestamp at which an exhausted cookie becomes usable again.
+    #[serde(default)]
+    pub reset_time: Option<i64>,
+}
+
+/// Why clewdr gave up on a cookie, either a bare reason or a reason with a timestamp.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum InvalidReason {
+    Plain(String),
+    Timed(BTreeMap<String, i64>),
+}
+
+impl Display for InvalidReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidReason::Plain(reason) => write!(f, "{reason}"),
+            InvalidReason::Timed(reasons) => {
+                let reasons = reasons
+                    .iter()
+                    .map(|(reason, time)| format!("{reason} (<t:{time}:R>)"))
+                    .collect::<Vec<_>>();
+                write!(f, "{}", reasons.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCookie {
+    pub cookie: String,
+    #[serde(default)]
+    pub reason: Option<InvalidReason>,
+}
+
+/// Snapshot of the cookie pool.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookiePool {
+    #[serde(default)]
+    pub valid: Vec<PooledCookie>,
+    #[serde(default)]
+    pub exhausted: Vec<PooledCookie>,
+    #[serde(default)]
+    pub invalid: Vec<InvalidCookie>,
+}
+
+impl CookiePool {
+    /// Every cookie in the pool, whatever its state.
+    pub fn cookies(&self) -> impl Iterator<Item = &str> {
+        self.valid
+            .iter()
+            .chain(self.exhausted.iter())
+            .map(|c| c.cookie.as_str())
+            .chain(self.invalid.iter().map(|c| c.cookie.as_str()))
+    }
+}
+
+#[derive(Serialize)]
+struct CookieBody<'a> {
+    cookie: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClewdrClient {
+    endpoint: Url,
+    secret: String,
+    http: Client,
+}
+
+impl ClewdrClient {
+    pub fn new(endpoint: Url, secret: impl Into<String>) -> Self {
+        Self {
+            endpoint,
+            secret: secret.into(),
+            http: Client::new(),
+        }
+    }
+
+    /// Client for the configured `cookie_endpoint`, if any.
+    pub fn from_cfg(cfg: &BotCfg) -> Option<Self> {
+        let endpoint = cfg.cookie_endpoint.to_owned()?;
+        Some(Self::new(endpoint, cfg.cookie_secret.to_owned()))
+    }
+
+    /// Submit a cookie to the pool.
+    pub async fn submit_cookie(&self, cookie: &str) -> Result<(), ClewdrError> {
+        self.send(Method::POST, "api/cookie", Some(&CookieBody { cookie }))
+            .await?;
+        Ok(())
+    }
+
+    /// Fetch the state of every cookie in the pool.
+    pub async fn cookie_pool(&self) -> Result<CookiePool, ClewdrError> {
+        self.fetch(Method::GET, "api/cookies", None).await
+    }
+
+    /// Remove a cookie from the pool.
+    pub async fn delete_cookie(&self, cookie: &str) -> Result<(), ClewdrError> {
+        self.send(Method::DELETE, "api/cookie", Some(&CookieBody { cookie }))
+            .await?;
+        Ok(())
+    }
+
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&CookieBody<'_>>,
+    ) -> Result<T, ClewdrError> {
+        self.send(method, path, body)
+            .await?
+            .json()
+            .await
+            .context(DecodeSnafu)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&CookieBody<'_>>,
+    ) -> Result<reqwest::Response, ClewdrError> {
+        let url = self.endpoint.join(path).context(EndpointSnafu)?;
+        let mut request = self.http.request(method, url).bearer_auth(&self.secret);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request.send().await.context(UnreachableSnafu)?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => UnauthorizedSnafu.fail(),
+            StatusCode::NOT_FOUND => NotFoundSnafu.fail(),
+            status => {
+                let message = response.text().await.unwrap_or_default();
+                StatusSnafu { status, message }.fail()
+            }
+        }
+    }
+}
+
+/// A local HTTP server answering with canned responses, for pointing clients at in tests.
+#[cfg(test)]
+pub(crate) mod stub {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::mpsc::{Receiver, channel},
+    };
+
+    use reqwest::Url;
+
+    /// Serve one `(status, body)` response per request, in order. The request lines received are
+    /// sent back through the returned channel.
+    pub(crate) fn serve(responses: Vec<(u16, &'static str)>) -> (Url, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 8192];
+                let n = stream.read(&mut buf).unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let _ = tx.send(request.lines().next().unwrap_or_default().to_owned());
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        (url, rx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cookie_pool() {
+        let (url, requests) = stub::serve(vec![
+            (
+                200,
+                r#"{"valid":[{"cookie":"sk-ant-sid01-a"}],
+                    "exhausted":[{"cookie":"sk-ant-sid01-b","reset_time":1700000000}],
+                    "invalid":[{"cookie":"sk-ant-sid01-c","reason":"Banned"},
+                               {"cookie":"sk-ant-sid01-d","reason":{"Restricted":1700000000}}]}"#,
+            ),
+            (401, ""),
+            (500, "boom"),
+        ]);
+        let client = ClewdrClient::new(url, "secret");
+
+        let pool = client.cookie_pool().await.unwrap();
+        assert_eq!(pool.valid.len(), 1);
+        assert_eq!(pool.exhausted[0].reset_time, Some(1700000000));
+        assert_eq!(
+            pool.invalid[0].reason,
+            Some(InvalidReason::Plain("Banned".into()))
+        );
+        assert_eq!(
+            pool.invalid[1].reason.as_ref().unwrap().to_string(),
+            "Restricted (<t:1700000000:R>)"
+        );
+        assert_eq!(pool.cookies().count(), 4);
+        assert_eq!(requests.recv().unwrap(), "GET /api/cookies HTTP/1.1");
+
+        let err = client.delete_cookie("sk-ant-sid01-a").await.unwrap_err();
+        assert!(matches!(err, ClewdrError::Unauthorized));
+        assert!(!err.is_transient() && !err.is_rejection());
+        assert_eq!(requests.recv().unwrap(), "DELETE /api/cookie HTTP/1.1");
+
+        let err = client.submit_cookie("sk-ant-sid01-a").await.unwrap_err();
+        assert!(err.is_transient());
+        assert_eq!(
+            err.to_string(),
+            "Clewdr returned 500 Internal Server Error: boom"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use poise::{CreateReply, command};
+use serenity::all::{AutocompleteChoice, CreateEmbed};
+use snafu::whatever;
+
+use super::{Context, check_admin};
+use crate::{
+    clewdr::{ClewdrClient, ClewdrError},
+    error::BotError,
+    utils::{hash_cookie, mask_secret},
+};
+
+const MAX_LISTED: usize = 25;
+
+fn clewdr_client(ctx: Context<'_>) -> Option<ClewdrClient> {
+    ClewdrClient::from_cfg(&ctx.data().cfg.load())
+}
+
+fn list_or_none(lines: Vec<String>) -> String {
+    if lines.is_empty() {
+        return "None".to_owned();
+    }
+    let more = lines.len().saturating_sub(MAX_LISTED);
+    let mut text = lines
+        .into_iter()
+        .take(MAX_LISTED)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if more > 0 {
+        text.push_str(&format!("\n... and {more} more"));
+    }
+    text
+}
+
+#[command(slash_command, ephemeral, check = "check_admin")]
+/// Show the state of the clewdr cookie pool
+pub async fn clewdr_status(
+    ctx: Context<'_>,
+    #[description = "List the masked cookies of each state, false by default"] list: Option<bool>,
+) -> Result<(), BotError> {
+    let Some(clewdr) = clewdr_client(ctx) else {
+        ctx.say("Cookie endpoint is not configured.").await?;
+        whatever!("Cookie endpoint is not configured");
+    };
+    let pool = match clewdr.cookie_pool().await {
+        Ok(pool) => pool,
+        Err(e) => {
+            ctx.say(format!("❌ {e}")).await?;
+            return Ok(());
+        }
+    };
+    let next_reset = pool.exhausted.iter().filter_map(|c| c.reset_time).min();
+    let mut embed = CreateEmbed::new()
+        .title("Clewdr cookie pool")
+        .field("Valid", pool.valid.len().to_string(), true)
+        .field("Exhausted", pool.exhausted.len().to_string(), true)
+        .field("Invalid", pool.invalid.len().to_string(), true)
+        .field(
+            "Next reset",
+            next_reset.map_or_else(|| "-".to_owned(), |t| format!("<t:{t}:R>")),
+            true,
+        )
+        .color(if pool.valid.is_empty() {
+            0xFF0000
+        } else if pool.exhausted.is_empty() && pool.invalid.is_empty() {
+            0x00FF00
+        } else {
+            0xFFFF00
+        });
+    if list.unwrap_or_default() {
+        let valid = pool
+            .valid
+            .iter()
+            .map(|c| format!("`{}`", mask_secret(&c.cookie)))
+            .collect();
+        let exhausted = pool
+            .exhausted
+            .iter()
+            .map(|c| match c.reset_time {
+                Some(t) => format!("`{}` resets <t:{t}:R>", mask_secret(&c.cookie)),
+                None => format!("`{}`", mask_secret(&c.cookie)),
+            })
+            .collect();
+        let invalid = pool
+            .invalid
+            .iter()
+            .map(|c| match &c.reason {
+                Some(reason) => format!("`{}` {reason}", mask_secret(&c.cookie)),
+                None => format!("`{}`", mask_secret(&c.cookie)),
+            })
+            .collect();
+        embed = embed
+            .field("Valid cookies", list_or_none(valid), false)
+            .field("Exhausted cookies", list_or_none(exhausted), false)
+            .field("Invalid cookies", list_or_none(invalid), false);
+    }
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Autocomplete runs before the command check, so it checks admins itself
+async fn pooled_cookie_choices<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = AutocompleteChoice> + 'a {
+    let is_admin = check_admin(ctx).await.unwrap_or(false);
+    let cookies = match clewdr_client(ctx).filter(|_| is_admin) {
+        Some(clewdr) => clewdr
+            .cookie_pool()
+            .await
+            .map(|pool| pool.cookies().map(str::to_owned).collect::<Vec<_>>())
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    cookies
+        .into_iter()
+        .map(|cookie| (mask_secret(&cookie), hash_cookie(&cookie)))
+        .filter(move |(masked, _)| masked.contains(partial))
+        .take(MAX_LISTED)
+        .map(|(masked, hash)| AutocompleteChoice::new(masked, hash))
+}
+
+#[command(slash_command, ephemeral, check = "check_admin")]
+/// Delete a cookie from the clewdr cookie pool
+pub async fn clewdr_delete(
+    ctx: Context<'_>,
+    #[description = "The cookie to delete"]
+    #[autocomplete = "pooled_cookie_choices"]
+    cookie: String,
+) -> Result<(), BotError> {
+    let Some(clewdr) = clewdr_client(ctx) else {
+        ctx.say("Cookie endpoint is not configured.").await?;
+        whatever!("Cookie endpoint is not configured");
+    };
+    let result = async {
+        // The choice carries a hash so the cookie itself never shows up in the command arguments
+        let pool = clewdr.cookie_pool().await?;
+        let Some(target) = pool
+            .cookies()
+            .find(|c| hash_cookie(c) == cookie || *c == cookie)
+        else {
+            return Err(ClewdrError::NotFound);
+        };
+        clewdr.delete_cookie(target).await?;
+        Ok(mask_secret(target))
+    }
+    .await;
+    match result {
+        Ok(masked) => {
+            ctx.say(format!("🗑️ Deleted `{masked}` from the pool."))
+                .await?
+        }
+        Err(e) => ctx.say(format!("❌ {e}")).await?,
+    };
+    Ok(())
+}
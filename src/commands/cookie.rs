@@ -1,9 +1,30 @@
-use poise::{CreateReply, command};
-use serenity::all::MessageBuilder;
-use snafu::{ResultExt, whatever};
+use chrono::Utc;
+use poise::{ChoiceParameter, CreateReply, Modal, command};
+use serenity::all::{Attachment, CreateEmbed, CreateMessage, Mentionable, Message};
+use snafu::whatever;
+use tracing::warn;
 
-use super::Context;
-use crate::error::BotError;
+use super::{
+    Context, check_admin,
+    stats::{TimeExpr, time_range, timestamp_choices},
+};
+use crate::{
+    clewdr::ClewdrClient,
+    error::BotError,
+    services::{collect_extra_texts, cookie_results_embed, process_cookie_outbox, submit_cookies},
+    utils::{CookieStatus, extract_cookies},
+};
+
+const MAX_FILE_SIZE: u32 = 1024 * 1024;
+
+#[derive(Debug, Modal)]
+#[name = "提交曲奇"]
+struct CookieModal {
+    #[name = "曲奇"]
+    #[placeholder = "粘贴要提交的曲奇, 可以包含多个, 格式要求很宽松"]
+    #[paragraph]
+    cookies: String,
+}
 
 #[command(
     slash_command,
@@ -11,54 +32,311 @@ use crate::error::BotError;
     description_localized("zh-CN", "提交曲奇给求封站"),
     ephemeral
 )]
-/// Submits a cookie to account banning site
+/// Submits cookies to account banning site, opens a form when no cookie is given
 pub async fn submit_cookie(
     ctx: Context<'_>,
     #[name_localized("zh-CN", "曲奇")]
-    #[description_localized("zh-CN", "要提交的曲奇内容, 格式要求很宽松")]
-    #[description = "The cookie content to submit, format is quite flexible"]
-    cookie: String,
+    #[description_localized("zh-CN", "要提交的曲奇内容, 可以包含多个曲奇, 格式要求很宽松")]
+    #[description = "The cookies to submit, may contain several, format is quite flexible"]
+    cookie: Option<String>,
+    #[name_localized("zh-CN", "文件")]
+    #[description_localized("zh-CN", "包含曲奇的文本文件")]
+    #[description = "A text file containing cookies"]
+    file: Option<Attachment>,
 ) -> Result<(), BotError> {
-    #[derive(serde::Serialize)]
-    struct CookieSubmission {
-        cookie: String,
-    }
-    let Some(url) = ctx.data().cfg.load().cookie_endpoint.to_owned() else {
+    let Some(clewdr) = ClewdrClient::from_cfg(&ctx.data().cfg.load()) else {
         ctx.say("Cookie endpoint is not configured.").await?;
         whatever!("Cookie endpoint is not configured");
     };
-    let reply = ctx.say("Submitting cookie...").await?;
-    let client = reqwest::Client::new();
-    if let Err(e) = client
-        .post(
-            url.join("api/cookie")
-                .whatever_context::<&str, BotError>("Failed to construct cookie submission URL")?,
+    let mut text = match (cookie, &file, ctx) {
+        (Some(cookie), ..) => cookie,
+        // Ask in a modal so the cookies stay out of the visible command arguments
+        (None, None, Context::Application(app_ctx)) => match CookieModal::execute(app_ctx).await? {
+            Some(modal) => modal.cookies,
+            None => return Ok(()),
+        },
+        (None, ..) => String::new(),
+    };
+    if let Some(file) = file {
+        if file.size > MAX_FILE_SIZE {
+            ctx.say(format!(
+                "❌ The file is too large, the limit is {} KiB.",
+                MAX_FILE_SIZE / 1024
+            ))
+            .await?;
+            return Ok(());
+        }
+        text.push('\n');
+        text.push_str(&String::from_utf8_lossy(&file.download().await?));
+    }
+    if extract_cookies(&text).is_empty() {
+        ctx.say("❌ No cookie found in the input.").await?;
+        return Ok(());
+    }
+    let reply = ctx.say("Submitting cookies...").await?;
+    let results = submit_cookies(
+        &ctx.data().db,
+        ctx.author().id,
+        ctx.guild_id(),
+        &clewdr,
+        &text,
+    )
+    .await;
+    reply
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content("")
+                .embed(cookie_results_embed(&results)),
         )
-        .json(&CookieSubmission { cookie })
-        .bearer_auth(ctx.data().cfg.load().cookie_secret.to_owned())
-        .send()
+        .await?;
+    Ok(())
+}
+
+#[command(
+    context_menu_command = "提交此曲奇",
+    guild_only,
+    ephemeral,
+    check = "check_admin"
+)]
+/// Submit the cookies leaked in a message on behalf of its author and delete the message once
+/// any of them was accepted or queued
+pub async fn submit_message_cookie(ctx: Context<'_>, message: Message) -> Result<(), BotError> {
+    let Some(clewdr) = ClewdrClient::from_cfg(&ctx.data().cfg.load()) else {
+        ctx.say("Cookie endpoint is not configured.").await?;
+        whatever!("Cookie endpoint is not configured");
+    };
+    let text = collect_extra_texts(ctx.http(), &message)
         .await
-        .and_then(|res| res.error_for_status())
+        .into_iter()
+        .fold(message.content.to_owned(), |text, extra| {
+            text + "\n" + &extra
+        });
+    if extract_cookies(&text).is_empty() {
+        ctx.say("❌ No cookie found in the message.").await?;
+        return Ok(());
+    }
+    let reply = ctx.say("Submitting cookies...").await?;
+    let results = submit_cookies(
+        &ctx.data().db,
+        message.author.id,
+        ctx.guild_id(),
+        &clewdr,
+        &text,
+    )
+    .await;
+    let embed = cookie_results_embed(&results);
+    // Nothing landed, keep the message so it can be submitted again
+    if !results
+        .iter()
+        .any(|(_, s)| matches!(s, CookieStatus::Accepted | CookieStatus::Queued))
     {
         reply
             .edit(
                 ctx,
-                CreateReply::default().content(
-                    MessageBuilder::new()
-                        .push("❌ Failed to submit cookie: ")
-                        .push_bold_safe(e.status().map_or_else(|| e.to_string(), |s| s.to_string()))
-                        .build(),
-                ),
+                CreateReply::default()
+                    .content("❌ No cookie was accepted, the message was kept.")
+                    .embed(embed),
             )
             .await?;
-        Err(e.into())
-    } else {
-        reply
-            .edit(
+        return Ok(());
+    }
+    let deleted = message.delete(ctx).await;
+    if let Err(e) = &deleted {
+        warn!("Failed to delete message with submitted cookie: {e}");
+    }
+    let notify = async || -> Result<(), BotError> {
+        message
+            .author
+            .id
+            .create_dm_channel(ctx)
+            .await?
+            .send_message(
                 ctx,
-                CreateReply::default().content("✅ Cookie submitted successfully!"),
+                CreateMessage::new()
+                    .content(format!(
+                        "🍪 {} 已代您提交了您在 {} 发送的曲奇, 原消息已删除。",
+                        ctx.author().mention(),
+                        message.channel_id.mention()
+                    ))
+                    .embed(embed.to_owned()),
             )
             .await?;
         Ok(())
+    };
+    if let Err(e) = notify().await {
+        warn!(
+            "Failed to notify {} about submitted cookie: {e}",
+            message.author.id
+        );
     }
+    reply
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content(if deleted.is_ok() {
+                    format!(
+                        "Submitted on behalf of {}, the message was deleted.",
+                        message.author.mention()
+                    )
+                } else {
+                    "❌ Submitted, but failed to delete the message.".to_owned()
+                })
+                .embed(embed),
+        )
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, ChoiceParameter)]
+pub enum QueueAction {
+    #[name = "status"]
+    Status,
+    #[name = "flush"]
+    Flush,
+    #[name = "purge"]
+    Purge,
+}
+
+#[command(slash_command, prefix_command, owners_only, ephemeral)]
+/// Show or manage the queue of cookie submissions waiting for the endpoint to come back
+pub async fn cookie_queue(
+    ctx: Context<'_>,
+    #[description = "status: show the queue, flush: retry everything now, purge: drop everything"]
+    action: Option<QueueAction>,
+) -> Result<(), BotError> {
+    let db = ctx.data().db.to_owned();
+    match action.unwrap_or(QueueAction::Status) {
+        QueueAction::Status => {
+            let depth = db.cookie_outbox().count().await?;
+            let mut embed = CreateEmbed::new()
+                .title("Cookie queue")
+                .field("Depth", depth.to_string(), true)
+                .color(if depth == 0 { 0x00FF00 } else { 0xFFFF00 });
+            if let Some(next) = db.cookie_outbox().next().await? {
+                embed = embed
+                    .field(
+                        "Next attempt",
+                        format!("<t:{}:R>", next.next_attempt_at().timestamp()),
+                        true,
+                    )
+                    .field("Attempts", next.attempts.to_string(), true)
+                    .field(
+                        "Last error",
+                        next.last_error.to_owned().unwrap_or_else(|| "-".into()),
+                        false,
+                    );
+            }
+            ctx.send(CreateReply::default().embed(embed)).await?;
+        }
+        QueueAction::Flush => {
+            let reply = ctx.say("Retrying queued cookies...").await?;
+            db.cookie_outbox().make_all_due(Utc::now()).await?;
+            let cfg = ctx.data().cfg.load_full();
+            let processed = process_cookie_outbox(&ctx.serenity_context().http, &db, &cfg).await?;
+            let remaining = db.cookie_outbox().count().await?;
+            reply
+                .edit(
+                    ctx,
+                    CreateReply::default().content(format!(
+                        "Retried {processed} cookies, {remaining} still queued."
+                    )),
+                )
+                .await?;
+        }
+        QueueAction::Purge => {
+            let purged = db.cookie_outbox().purge().await?;
+            ctx.say(format!("Purged {purged} queued cookies.")).await?;
+        }
+    }
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    name_localized("zh-CN", "曲奇统计"),
+    description_localized("zh-CN", "查看曲奇贡献记录和排行榜"),
+    ephemeral
+)]
+/// Show your cookie contributions and the contributor leaderboard of this server
+pub async fn cookie_stats(
+    ctx: Context<'_>,
+    #[description = "Start of the time range, e.g. today, 7d or 2025-07-01..2025-07-15, unlimited by default"]
+    #[description_localized(
+        "zh-CN",
+        "统计时间范围开始时间, 如 today、7d、2025-07-01..2025-07-15, 默认无限制"
+    )]
+    #[autocomplete = "timestamp_choices"]
+    from: Option<TimeExpr>,
+    #[description = "End of the time range, e.g. yesterday or 2025-07-15, now by default"]
+    #[description_localized("zh-CN", "统计时间范围结束时间, 如 yesterday、2025-07-15, 默认为现在")]
+    #[autocomplete = "timestamp_choices"]
+    to: Option<TimeExpr>,
+) -> Result<(), BotError> {
+    let (from, to) = time_range(ctx, from, to)?;
+    const HISTORY_SIZE: u64 = 10;
+    const LEADERBOARD_SIZE: u64 = 10;
+    let guild_id = ctx
+        .guild_id()
+        .expect("Guild ID should be present in a guild context");
+    let ledger = ctx.data().db.cookie_ledger();
+    let history = ledger
+        .history(ctx.author().id, HISTORY_SIZE)
+        .await?
+        .into_iter()
+        .map(|s| {
+            format!(
+                "<t:{}:R> `{}` {}",
+                s.created_at().timestamp(),
+                &s.cookie_hash[..8],
+                s.status
+            )
+        })
+        .collect::<Vec<_>>();
+    let leaderboard = ledger
+        .leaderboard(guild_id, from, to, LEADERBOARD_SIZE)
+        .await?
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            format!(
+                "{}. {} - {} accepted / {} submitted",
+                i + 1,
+                c.user_id.mention(),
+                c.accepted,
+                c.total
+            )
+        })
+        .collect::<Vec<_>>();
+    let totals = ledger
+        .totals(guild_id, from, to)
+        .await?
+        .into_iter()
+        .map(|(status, count)| format!("{status}: {count}"))
+        .collect::<Vec<_>>();
+    let or_none = |lines: Vec<String>| {
+        if lines.is_empty() {
+            "None".to_owned()
+        } else {
+            lines.join("\n")
+        }
+    };
+    let embed = CreateEmbed::new()
+        .title("Cookie contributions")
+        .field("Your latest submissions", or_none(history), false)
+        .field("Leaderboard", or_none(leaderboard), false)
+        .field("Totals", or_none(totals), false)
+        .field(
+            "Time range",
+            format!(
+                "{} - {}",
+                from.map_or_else(|| "unlimited".into(), |f| f.to_rfc3339()),
+                to.map_or_else(|| "unlimited".into(), |t| t.to_rfc3339())
+            ),
+            false,
+        )
+        .color(0x00FF00);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
 }
@@ -1,12 +1,14 @@
-mod cookie;
+mod clewdr;
+pub mod cookie;
 pub mod flush;
-mod stats;
+pub mod stats;
 mod tree_hole;
 mod utils;
 
 use std::sync::Arc;
 
 use arc_swap::ArcSwap;
+use clewdr::*;
 use cookie::*;
 use flush::*;
 use owo_colors::OwoColorize;
@@ -56,6 +58,21 @@ async fn on_error(error: poise::FrameworkError<'_, Data, BotError>) {
         poise::FrameworkError::Command { error, ctx, .. } => {
             error!("Error in command `{}`: {}", ctx.command().name, error);
         }
+        poise::FrameworkError::CooldownHit {
+            remaining_cooldown,
+            ctx,
+            ..
+        } => {
+            let reply = poise::CreateReply::default()
+                .content(format!(
+                    "⏳ 操作过于频繁, 请在 {} 秒后重试。",
+                    remaining_cooldown.as_secs() + 1
+                ))
+                .ephemeral(true);
+            if let Err(e) = ctx.send(reply).await {
+                error!("Error while handling error: {}", e)
+            }
+        }
         error => {
             if let Err(e) = poise::builtins::on_error(error).await {
                 error!("Error while handling error: {}", e)
@@ -76,15 +93,30 @@ fn option(cfg: &ArcSwap<BotCfg>) -> poise::FrameworkOptions<Data, BotError> {
             register(),
             system_info(),
             submit_cookie(),
+            submit_message_cookie(),
+            cookie_queue(),
+            cookie_stats(),
+            clewdr_status(),
+            clewdr_delete(),
             register_tree_hole(),
             unregister_tree_hole(),
             list_tree_holes(),
             flush_message(),
             channel_stats(),
             user_stats(),
+            user_detail(),
+            activity_chart(),
+            activity_heatmap(),
+            engagement(),
+            digest_set(),
+            digest_disable(),
+            stats_export(),
+            my_stats(),
+            backfill_stats(),
             ping(),
             help(),
             vacuum(),
+            prune(),
         ],
         prefix_options: PrefixFrameworkOptions {
             prefix: "!".to_string().into(),
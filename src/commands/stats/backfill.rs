@@ -0,0 +1,373 @@
+use std::{
+    collections::HashSet,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use poise::command;
+use serenity::all::*;
+use tracing::warn;
+
+use super::{super::Context, TimeExpr, time_range, timestamp_choices};
+use crate::{
+    config::BotCfg,
+    database::BotDatabase,
+    error::BotError,
+    handlers::retention_cutoff,
+    utils::{Archive, get_archived_threads, get_children_channels},
+};
+
+const PAGE_SIZE: u8 = 100;
+/// Pause between two history requests, on top of serenity's own rate limiting
+const THROTTLE: Duration = Duration::from_millis(500);
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Default)]
+struct Progress {
+    since: DateTime<Utc>,
+    channels_done: usize,
+    channels_total: usize,
+    current: Option<ChannelId>,
+    recorded: u64,
+    oldest: Option<Timestamp>,
+    failed: Vec<ChannelId>,
+}
+
+impl Progress {
+    fn render(&self, finished: bool) -> String {
+        let mut lines = vec![
+            format!(
+                "{} 回填至 <t:{}:f>",
+                if finished {
+                    "✅ 已完成"
+                } else {
+                    "⏳ 正在"
+                },
+                self.since.timestamp()
+            ),
+            format!("频道: {}/{}", self.channels_done, self.channels_total),
+            format!("已记录消息: {}", self.recorded),
+        ];
+        if let Some(current) = self.current.filter(|_| !finished) {
+            lines.push(format!("当前频道: {}", current.mention()));
+        }
+        if let Some(oldest) = self.oldest {
+            lines.push(format!("最早消息: <t:{}:R>", oldest.unix_timestamp()));
+        }
+        if !self.failed.is_empty() {
+            lines.push(format!(
+                "失败的频道: {}",
+                self.failed
+                    .iter()
+                    .map(|c| c.mention().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// The progress message, edited at most once per [`PROGRESS_INTERVAL`].
+struct Reporter<'a> {
+    http: &'a Http,
+    message: Message,
+    last: Instant,
+}
+
+impl Reporter<'_> {
+    async fn report(&mut self, progress: &Progress, finished: bool) {
+        if !finished && self.last.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last = Instant::now();
+        if let Err(e) = self
+            .message
+            .edit(
+                self.http,
+                EditMessage::new().content(progress.render(finished)),
+            )
+            .await
+        {
+            warn!("Failed to update backfill progress: {e}");
+        }
+    }
+}
+
+/// Channels and threads holding messages in `guild_id`, limited to `root` and everything under it
+async fn crawl_targets(
+    http: &Http,
+    guild_id: GuildId,
+    root: Option<&GuildChannel>,
+    since: DateTime<Utc>,
+) -> Result<Vec<GuildChannel>, BotError> {
+    let mut channels = match root {
+        Some(root) => get_children_channels(http, guild_id, root).await?,
+        None => guild_id.channels(http).await?.into_values().collect(),
+    };
+    let parents = channels.iter().map(|c| c.id).collect::<HashSet<_>>();
+    let mut threads = guild_id.get_active_threads(http).await?.threads;
+    for channel in channels.iter().filter(|c| {
+        matches!(
+            c.kind,
+            ChannelType::Text | ChannelType::News | ChannelType::Forum
+        )
+    }) {
+        // An archived thread has no message newer than its archival
+        let recent = |t: &GuildChannel| {
+            t.thread_metadata
+                .and_then(|m| m.archive_timestamp)
+                .is_none_or(|archived_at| *archived_at >= since)
+        };
+        for archive in [Archive::Public, Archive::Private] {
+            match get_archived_threads(http, channel.id, archive, |page| page.iter().all(recent))
+                .await
+            {
+                Ok(archived) => threads.extend(archived.into_iter().filter(recent)),
+                Err(e) => warn!("Failed to list archived threads of {}: {e}", channel.id),
+            }
+        }
+    }
+    threads.retain(|t| t.parent_id.is_some_and(|p| parents.contains(&p)));
+    channels.extend(threads);
+    channels.retain(|c| {
+        matches!(
+            c.kind,
+            ChannelType::Text
+                | ChannelType::News
+                | ChannelType::Voice
+                | ChannelType::Stage
+                | ChannelType::PublicThread
+                | ChannelType::PrivateThread
+                | ChannelType::NewsThread
+        )
+    });
+    let mut seen = HashSet::new();
+    channels.retain(|c| seen.insert(c.id));
+    Ok(channels)
+}
+
+/// Crawl the history of a channel back to `since`, resuming from its stored cursor
+async fn crawl_channel(
+    http: &Http,
+    db: &BotDatabase,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    progress: &mut Progress,
+    reporter: &mut Reporter<'_>,
+) -> Result<(), BotError> {
+    let since = progress.since;
+    let cursor = db.backfill().cursor(channel_id).await?;
+    if cursor
+        .as_ref()
+        .is_some_and(|c| c.done && c.until() <= since)
+    {
+        return Ok(());
+    }
+    let mut before = cursor.and_then(|c| c.oldest_message_id());
+    loop {
+        let mut request = GetMessages::new().limit(PAGE_SIZE);
+        if let Some(before) = before {
+            request = request.before(before);
+        }
+        let batch = channel_id.messages(http, request).await?;
+        let reached = record_page(db, guild_id, channel_id, &batch, &mut before, progress).await?
+            || batch.len() < PAGE_SIZE as usize;
+        db.backfill()
+            .save(guild_id, channel_id, before, since, reached)
+            .await?;
+        reporter.report(progress, false).await;
+        if reached {
+            return Ok(());
+        }
+        tokio::time::sleep(THROTTLE).await;
+    }
+}
+
+/// Record a page of history, newest first, back to `progress.since`. Returns whether it got
+/// there, `before` is left at the oldest message crawled.
+async fn record_page(
+    db: &BotDatabase,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    batch: &[Message],
+    before: &mut Option<MessageId>,
+    progress: &mut Progress,
+) -> Result<bool, BotError> {
+    // Newest first, so everything after the first message older than `since` is older too
+    for message in batch {
+        if *message.timestamp < progress.since {
+            return Ok(true);
+        }
+        *before = Some(message.id);
+        progress.oldest = Some(message.timestamp);
+        if message.author.bot || message.author.system {
+            continue;
+        }
+        db.message()
+            .record(
+                message.id,
+                message.author.id,
+                guild_id,
+                channel_id,
+                message.timestamp,
+            )
+            .await?;
+        progress.recorded += 1;
+    }
+    Ok(false)
+}
+
+/// How far back a backfill may go. Pruned messages are still counted through their rollups,
+/// recording them again would count them twice.
+async fn backfill_since(
+    db: &BotDatabase,
+    cfg: &BotCfg,
+    since: DateTime<Utc>,
+) -> Result<DateTime<Utc>, BotError> {
+    Ok(retention_cutoff(db, cfg)
+        .await?
+        .map_or(since, |cutoff| since.max(cutoff)))
+}
+
+#[command(slash_command, prefix_command, guild_only, owners_only, ephemeral)]
+/// 从频道历史回填消息活跃度数据，中断后重新运行即可从上次的位置继续
+pub async fn backfill_stats(
+    ctx: Context<'_>,
+    #[description = "回填到此时间为止, 如 2025-07-01 或 30d"]
+    #[autocomplete = "timestamp_choices"]
+    since: TimeExpr,
+    #[description = "只回填该频道及其子频道和子区, 默认为整个服务器"] channel: Option<GuildChannel>,
+) -> Result<(), BotError> {
+    let Some(since) = time_range(ctx, Some(since), None)?.0 else {
+        ctx.say("❌ 请指定回填的起始时间。").await?;
+        return Ok(());
+    };
+    let since = backfill_since(&ctx.data().db, &ctx.data().cfg.load(), since).await?;
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        ctx.say("❌ 已有回填任务正在运行。").await?;
+        return Ok(());
+    }
+    let result = async {
+        let guild_id = ctx.guild_id().expect("guild_only command");
+        let http = ctx.serenity_context().http.as_ref();
+        ctx.say("正在收集需要回填的频道...").await?;
+        let targets = crawl_targets(http, guild_id, channel.as_ref(), since).await?;
+        let mut progress = Progress {
+            since,
+            channels_total: targets.len(),
+            ..Default::default()
+        };
+        // A regular message, the interaction token expires long before a large backfill finishes
+        let message = ctx.channel_id().say(http, progress.render(false)).await?;
+        let mut reporter = Reporter {
+            http,
+            message,
+            last: Instant::now(),
+        };
+        let db = ctx.data().db.to_owned();
+        for target in targets {
+            progress.current = Some(target.id);
+            if let Err(e) =
+                crawl_channel(http, &db, guild_id, target.id, &mut progress, &mut reporter).await
+            {
+                warn!("Failed to backfill {}: {e}", target.id);
+                progress.failed.push(target.id);
+            }
+            progress.channels_done += 1;
+        }
+        reporter.report(&progress, true).await;
+        Ok::<_, BotError>(())
+    }
+    .await;
+    RUNNING.store(false, Ordering::SeqCst);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use migration::{Migrator, MigratorTrait};
+
+    use super::*;
+    use crate::handlers::prune_messages;
+
+    #[tokio::test]
+    async fn test_backfill_rolled_up_days() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let (guild_id, channel_id) = (GuildId::new(1), ChannelId::new(1));
+        let now = Utc::now();
+        // One message a day over the last 20 days, newest first like the history endpoint
+        let history = (0..20)
+            .map(|i| {
+                let mut message = Message::default();
+                message.id = MessageId::new(100 - i);
+                message.author.id = UserId::new(1);
+                message.timestamp = (now - chrono::Duration::days(i as i64)).into();
+                message
+            })
+            .collect::<Vec<_>>();
+        for message in &history {
+            db.message()
+                .record(
+                    message.id,
+                    message.author.id,
+                    guild_id,
+                    channel_id,
+                    message.timestamp,
+                )
+                .await
+                .unwrap();
+        }
+        db.rollup()
+            .roll_up((now - chrono::Duration::days(1)).date_naive())
+            .await
+            .unwrap();
+        let cfg = BotCfg {
+            message_retention_days: Some(5),
+            ..Default::default()
+        };
+        assert!(prune_messages(&db, &cfg).await.unwrap() > 0);
+        let count = async || {
+            db.message()
+                .get_channel_stats(guild_id, None::<DateTime<Utc>>, None::<DateTime<Utc>>)
+                .await
+                .unwrap()
+        };
+        assert_eq!(count().await, vec![(channel_id, 20)]);
+
+        // Backfilling past the pruned days leaves the rolled up counts alone
+        let mut progress = Progress {
+            since: backfill_since(&db, &cfg, now - chrono::Duration::days(30))
+                .await
+                .unwrap(),
+            ..Default::default()
+        };
+        let mut before = None;
+        assert!(
+            record_page(
+                &db,
+                guild_id,
+                channel_id,
+                &history,
+                &mut before,
+                &mut progress
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(count().await, vec![(channel_id, 20)]);
+        assert_eq!(progress.recorded, 5);
+
+        // A nuke forgets the crawl, the next backfill starts from the newest message again
+        db.backfill()
+            .save(guild_id, channel_id, before, progress.since, true)
+            .await
+            .unwrap();
+        db.message().nuke().await.unwrap();
+        assert!(db.backfill().cursor(channel_id).await.unwrap().is_none());
+    }
+}
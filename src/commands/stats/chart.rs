@@ -0,0 +1,366 @@
+use std::{io::Cursor, sync::Once};
+
+use chrono::{
+    DateTime, Datelike, Days, FixedOffset, NaiveDateTime, NaiveTime, TimeDelta, Timelike, Utc,
+};
+use image::{ImageFormat, RgbImage};
+use plotters::{
+    coord::Shift,
+    prelude::{
+        BitMapBackend, ChartBuilder, DrawingArea, IntoDrawingArea, LineSeries, RGBColor, Rectangle,
+        WHITE,
+    },
+    style::{Color as _, FontStyle, register_font},
+};
+use poise::{ChoiceParameter, CreateReply, command};
+use serenity::all::{colours::roles::DARK_GREEN, *};
+use snafu::{OptionExt, ResultExt};
+
+use super::{
+    super::{Context, check_admin},
+    TimeExpr, channel_filter, guild_choices, local_offset, time_range, timestamp_choices,
+};
+use crate::{error::BotError, repo::HourlyActivity};
+
+const CHART_SIZE: (u32, u32) = (1200, 600);
+pub(super) const MAX_BUCKETS: i64 = 1000;
+const FILE_NAME: &str = "activity.png";
+/// Family the embedded chart font is registered under
+pub(super) const FONT: &str = "Noto Sans";
+static FONT_DATA: &[u8] = include_bytes!("../../../assets/fonts/NotoSans-Regular.ttf");
+
+/// Time span covered by one point of an activity chart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+pub enum Bucket {
+    #[name = "按小时"]
+    Hour,
+    #[default]
+    #[name = "按天"]
+    Day,
+    #[name = "按周"]
+    Week,
+}
+
+impl Bucket {
+    pub(super) fn span(self) -> TimeDelta {
+        match self {
+            Bucket::Hour => TimeDelta::hours(1),
+            Bucket::Day => TimeDelta::days(1),
+            Bucket::Week => TimeDelta::weeks(1),
+        }
+    }
+
+    /// Time range charted when none is given
+    fn default_range(self) -> TimeDelta {
+        match self {
+            Bucket::Hour => TimeDelta::days(2),
+            Bucket::Day => TimeDelta::days(30),
+            Bucket::Week => TimeDelta::weeks(26),
+        }
+    }
+
+    /// Start of the bucket holding `time`, weeks start on Monday
+    fn start(self, time: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            Bucket::Hour => time
+                .date()
+                .and_time(NaiveTime::from_hms_opt(time.hour(), 0, 0).expect("valid hour")),
+            Bucket::Day => time.date().and_time(NaiveTime::MIN),
+            Bucket::Week => (time.date() - Days::new(time.weekday().num_days_from_monday().into()))
+                .and_time(NaiveTime::MIN),
+        }
+    }
+
+    fn label(self, start: NaiveDateTime) -> String {
+        match self {
+            Bucket::Hour => start.format("%m-%d %H:00").to_string(),
+            Bucket::Day | Bucket::Week => start.format("%m-%d").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+pub enum ChartStyle {
+    #[default]
+    #[name = "折线图"]
+    Line,
+    #[name = "柱状图"]
+    Bar,
+}
+
+/// Sum hourly counts into the local time buckets between `from` and `to`, empty buckets included.
+///
+/// Counts before `from` go to the first bucket, they come from the rollups of a partial day.
+pub(super) fn bucket_counts(
+    hourly: &[(DateTime<Utc>, u64)],
+    bucket: Bucket,
+    offset: FixedOffset,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<(NaiveDateTime, u64)> {
+    let local = |time: DateTime<Utc>| time.with_timezone(&offset).naive_local();
+    let mut buckets = Vec::new();
+    let mut start = bucket.start(local(from));
+    while start < local(to) {
+        buckets.push((start, 0));
+        start += bucket.span();
+    }
+    for &(time, count) in hourly.iter().filter(|(time, _)| *time < to) {
+        let time = local(time.max(from));
+        let index = buckets.partition_point(|(start, _)| *start <= time);
+        if let Some((_, total)) = index.checked_sub(1).and_then(|i| buckets.get_mut(i)) {
+            *total += count;
+        }
+    }
+    buckets
+}
+
+/// Footer for charts reaching back into days only kept as rollups, whose hours are estimates
+pub(super) fn spread_footer(
+    hourly: &HourlyActivity,
+    from: DateTime<Utc>,
+) -> Option<CreateEmbedFooter> {
+    let until = hourly.spread_until.filter(|until| *until > from)?;
+    Some(CreateEmbedFooter::new(format!(
+        "{} (UTC) 之前的消息只保留了每日总数, 已平均分配到当天各小时, 按小时及本地日期的统计为估算值",
+        until.format("%Y-%m-%d")
+    )))
+}
+
+pub(super) type DrawResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+/// Draw onto a white canvas of `size` and encode it as a PNG image
+pub(super) fn render_png(
+    size: (u32, u32),
+    draw: impl FnOnce(&DrawingArea<BitMapBackend, Shift>) -> DrawResult,
+) -> Result<Vec<u8>, BotError> {
+    static REGISTER_FONT: Once = Once::new();
+    REGISTER_FONT.call_once(|| {
+        assert!(
+            register_font(FONT, FontStyle::Normal, FONT_DATA).is_ok(),
+            "Embedded chart font should be valid"
+        );
+    });
+    let (width, height) = size;
+    let mut pixels = vec![0; (width * height * 3) as usize];
+    paint(&mut pixels, size, draw).whatever_context::<_, BotError>("Failed to draw the chart")?;
+    let image = RgbImage::from_raw(width, height, pixels)
+        .whatever_context::<_, BotError>("Invalid chart buffer")?;
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .whatever_context::<_, BotError>("Failed to encode the chart")?;
+    Ok(png)
+}
+
+fn paint(
+    pixels: &mut [u8],
+    size: (u32, u32),
+    draw: impl FnOnce(&DrawingArea<BitMapBackend, Shift>) -> DrawResult,
+) -> DrawResult {
+    let root = BitMapBackend::with_buffer(pixels, size).into_drawing_area();
+    root.fill(&WHITE)?;
+    draw(&root)?;
+    root.present()?;
+    Ok(())
+}
+
+fn draw(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    buckets: &[(NaiveDateTime, u64)],
+    bucket: Bucket,
+    style: ChartStyle,
+) -> DrawResult {
+    let max = buckets.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let mut chart = ChartBuilder::on(root)
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(-0.5..buckets.len() as f64 - 0.5, 0..(max + max / 10).max(1))?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(12)
+        .x_label_formatter(&|x| {
+            let index = x.round();
+            if (x - index).abs() > f64::EPSILON || index < 0.0 {
+                return String::new();
+            }
+            buckets
+                .get(index as usize)
+                .map_or_else(String::new, |(start, _)| bucket.label(*start))
+        })
+        .label_style((FONT, 16))
+        .draw()?;
+    let color = RGBColor(0x1f, 0x8b, 0x4c);
+    let points = buckets
+        .iter()
+        .enumerate()
+        .map(|(i, (_, count))| (i as f64, *count));
+    match style {
+        ChartStyle::Line => {
+            chart.draw_series(LineSeries::new(points, color.stroke_width(2)))?;
+        }
+        ChartStyle::Bar => {
+            chart.draw_series(points.map(|(x, count)| {
+                Rectangle::new([(x - 0.4, 0), (x + 0.4, count)], color.filled())
+            }))?;
+        }
+    }
+    Ok(())
+}
+
+/// Render the buckets as a PNG image
+pub(super) fn render_chart(
+    buckets: &[(NaiveDateTime, u64)],
+    bucket: Bucket,
+    style: ChartStyle,
+) -> Result<Vec<u8>, BotError> {
+    render_png(CHART_SIZE, |root| draw(root, buckets, bucket, style))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[command(slash_command, guild_only, ephemeral, check = "check_admin")]
+/// 绘制消息活跃度趋势图
+pub async fn activity_chart(
+    ctx: Context<'_>,
+    #[description = "统计粒度, 默认按天"] bucket: Option<Bucket>,
+    #[description = "图表样式, 默认为折线图"] style: Option<ChartStyle>,
+    #[description = "指定服务器 ID, 默认为当前所在服务器"]
+    #[autocomplete = "guild_choices"]
+    guild: Option<Guild>,
+    #[description = "只统计该频道及其子频道"] channel: Option<GuildChannel>,
+    #[description = "只统计该用户"] user: Option<User>,
+    #[description = "统计时间范围开始时间, 如 today、7d、2025-07-01..2025-07-15, 默认按粒度取最近一段时间"]
+    #[autocomplete = "timestamp_choices"]
+    from: Option<TimeExpr>,
+    #[description = "统计时间范围结束时间, 如 yesterday、2025-07-15, 默认为现在"]
+    #[autocomplete = "timestamp_choices"]
+    to: Option<TimeExpr>,
+    #[description = "是否为临时消息（仅自己可见）"] ephemeral: Option<bool>,
+) -> Result<(), BotError> {
+    let (from, to) = time_range(ctx, from, to)?;
+    let bucket = bucket.unwrap_or_default();
+    let style = style.unwrap_or_default();
+    let ephemeral = ephemeral.unwrap_or(true);
+    let to = to.unwrap_or_else(Utc::now);
+    let from = from.unwrap_or(to - bucket.default_range());
+    if from >= to {
+        ctx.say("❌ 开始时间必须早于结束时间。").await?;
+        return Ok(());
+    }
+    if (to - from).num_seconds() / bucket.span().num_seconds() > MAX_BUCKETS {
+        ctx.say(format!(
+            "❌ 时间范围过大, 最多绘制 {MAX_BUCKETS} 个数据点, 请缩小范围或增大统计粒度。"
+        ))
+        .await?;
+        return Ok(());
+    }
+    if ephemeral {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
+    let guild_id = guild
+        .map(|g| g.id)
+        .or_else(|| ctx.guild_id())
+        .expect("Guild ID should be present in a guild context");
+    let guild_name = guild_id.name(ctx).unwrap_or_else(|| guild_id.to_string());
+    let channels = channel_filter(ctx, guild_id, channel.as_ref()).await?;
+    let hourly = ctx
+        .data()
+        .db
+        .message()
+        .get_hourly_activity(
+            guild_id,
+            channels.as_deref(),
+            user.as_ref().map(|u| u.id),
+            Some(from),
+            Some(to),
+        )
+        .await?;
+    let footer = spread_footer(&hourly, from);
+    let buckets = bucket_counts(&hourly.counts, bucket, local_offset(ctx)?, from, to);
+    let sum = buckets.iter().map(|(_, count)| *count).sum::<u64>();
+    let png = tokio::task::spawn_blocking(move || render_chart(&buckets, bucket, style))
+        .await
+        .whatever_context::<_, BotError>("Chart rendering panicked")??;
+
+    let mut embed = CreateEmbed::default()
+        .title(format!("{guild_name} 消息活跃度趋势"))
+        .field("总条数", sum.to_string(), true)
+        .field("统计粒度", bucket.name(), true)
+        .field(
+            "统计时间范围",
+            format!("<t:{}:f> - <t:{}:f>", from.timestamp(), to.timestamp()),
+            false,
+        )
+        .image(format!("attachment://{FILE_NAME}"))
+        .color(DARK_GREEN);
+    if let Some(channel) = channel {
+        embed = embed.field("频道", channel.mention().to_string(), true);
+    }
+    if let Some(user) = user {
+        embed = embed.field("用户", user.mention().to_string(), true);
+    }
+    if let Some(footer) = footer {
+        embed = embed.footer(footer);
+    }
+    let reply = CreateReply::default()
+        .embed(embed)
+        .attachment(CreateAttachment::bytes(png, FILE_NAME))
+        .ephemeral(ephemeral);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bucket_counts() {
+        let at = |day: u32, hour: u32| {
+            chrono::NaiveDate::from_ymd_opt(2026, 10, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        // UTC+8, so 16:00 UTC starts the next local day
+        let offset = FixedOffset::east_opt(8 * 3600).unwrap();
+        let hourly = [
+            (at(12, 15), 1),
+            (at(12, 16), 2),
+            (at(13, 3), 4),
+            (at(19, 20), 8),
+        ];
+        let days = bucket_counts(&hourly, Bucket::Day, offset, at(11, 16), at(14, 16));
+        let counts = days.iter().map(|(_, count)| *count).collect::<Vec<_>>();
+        assert_eq!(counts, [1, 6, 0]);
+        assert_eq!(days[0].0, at(12, 0).naive_utc());
+
+        // 2026-10-12 is a Monday
+        let weeks = bucket_counts(&hourly, Bucket::Week, offset, at(12, 0), at(20, 0));
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(weeks[0], (at(12, 0).naive_utc(), 7));
+        assert_eq!(weeks[1], (at(19, 0).naive_utc(), 8));
+
+        let hours = bucket_counts(&hourly, Bucket::Hour, offset, at(12, 15), at(12, 17));
+        assert_eq!(hours.iter().map(|(_, c)| *c).collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn test_render_chart() {
+        let start = chrono::NaiveDate::from_ymd_opt(2026, 10, 12)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let buckets = (0..7)
+            .map(|i| (start + Days::new(i), i * 3))
+            .collect::<Vec<_>>();
+        // Labels are drawn with the embedded font, no system font is needed
+        let png = render_chart(&buckets, Bucket::Day, ChartStyle::Bar).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+}
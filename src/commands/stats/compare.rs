@@ -0,0 +1,126 @@
+use std::{cmp::Ordering, collections::HashMap, hash::Hash};
+
+use chrono::{DateTime, Utc};
+use serenity::all::Mentionable;
+
+use crate::repo::Activity;
+
+/// Most entries listed as gone from the ranking
+const MAX_GONE: usize = 10;
+
+/// The window of the same length right before `from..to`
+pub(super) fn previous_window(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    (from - (to - from), from)
+}
+
+/// Change from `previous` to `current`, with the percentage when there was something before
+pub(super) fn count_delta(current: u64, previous: u64) -> String {
+    let diff = current as i64 - previous as i64;
+    if previous == 0 {
+        return format!("{diff:+}");
+    }
+    format!("{diff:+}, {:+.1}%", diff as f64 * 100.0 / previous as f64)
+}
+
+/// The ranking of the previous window, to compare the current ranking with
+pub(super) struct Comparison<T> {
+    /// Rank, starting at 1, and count of every entry of the previous ranking
+    previous: HashMap<T, (usize, u64)>,
+    total: u64,
+}
+
+impl<T: Copy + Eq + Hash> Comparison<T> {
+    pub fn new(previous: &[(T, u64, Activity)]) -> Self {
+        Self {
+            previous: previous
+                .iter()
+                .enumerate()
+                .map(|(i, (id, count, _))| (*id, (i + 1, *count)))
+                .collect(),
+            total: previous.iter().map(|(_, count, _)| *count).sum(),
+        }
+    }
+
+    /// Total of the previous ranking
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Rank movement and count change of an entry ranked `rank` with `count` now
+    pub fn delta(&self, id: &T, rank: usize, count: u64) -> String {
+        let Some(&(previous_rank, previous_count)) = self.previous.get(id) else {
+            return "🆕".to_owned();
+        };
+        let arrow = match rank.cmp(&previous_rank) {
+            Ordering::Less => format!("⬆️{}", previous_rank - rank),
+            Ordering::Greater => format!("⬇️{}", rank - previous_rank),
+            Ordering::Equal => "➖".to_owned(),
+        };
+        format!("{arrow} ({})", count_delta(count, previous_count))
+    }
+
+    /// Entries of the previous ranking missing from `current`, by their previous rank, and how
+    /// many there are
+    pub fn gone<'a>(&self, current: impl IntoIterator<Item = &'a T>) -> (Vec<(T, u64)>, usize)
+    where
+        T: 'a,
+    {
+        let mut gone = self.previous.to_owned();
+        for id in current {
+            gone.remove(id);
+        }
+        let mut gone = gone.into_iter().collect::<Vec<_>>();
+        gone.sort_unstable_by_key(|(_, (rank, _))| *rank);
+        let count = gone.len();
+        (
+            gone.into_iter()
+                .take(MAX_GONE)
+                .map(|(id, (_, count))| (id, count))
+                .collect(),
+            count,
+        )
+    }
+}
+
+/// List the entries gone from the ranking with their previous counts, `count` of them in total
+pub(super) fn gone_text<T: Mentionable>(gone: Vec<(T, u64)>, count: usize) -> String {
+    let mut text = gone
+        .iter()
+        .map(|(id, previous)| format!("{} (上期 {previous} 条)", id.mention()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if count > gone.len() {
+        text.push_str(&format!("\n等共 {count} 个"));
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_comparison() {
+        let entry = |id: u64, count: u64| {
+            (
+                id,
+                count,
+                Activity {
+                    total: count,
+                    deleted: 0,
+                },
+            )
+        };
+        let comparison = Comparison::new(&[entry(1, 10), entry(2, 8), entry(3, 5)]);
+        assert_eq!(comparison.total(), 23);
+        assert_eq!(comparison.delta(&2, 1, 12), "⬆️1 (+4, +50.0%)");
+        assert_eq!(comparison.delta(&1, 2, 5), "⬇️1 (-5, -50.0%)");
+        assert_eq!(comparison.delta(&4, 3, 1), "🆕");
+        assert_eq!(comparison.delta(&3, 3, 5), "➖ (+0, +0.0%)");
+        assert_eq!(comparison.gone(&[2, 4]), (vec![(1, 10), (3, 5)], 2));
+        assert_eq!(count_delta(3, 0), "+3");
+    }
+}
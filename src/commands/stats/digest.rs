@@ -0,0 +1,253 @@
+use std::hash::Hash;
+
+use chrono::{DateTime, Datelike, Days, FixedOffset, NaiveDate, NaiveTime, Utc};
+use poise::{ChoiceParameter, command};
+use serenity::all::{colours::roles::DARK_GREEN, *};
+
+use super::{
+    super::{Context, check_admin},
+    DeletedMessages,
+    compare::{Comparison, count_delta, previous_window},
+    local_offset,
+};
+use crate::{
+    config::{BotCfg, DigestCfg, DigestPeriod},
+    database::BotDatabase,
+    error::BotError,
+    repo::Activity,
+};
+
+/// Channels and users listed in a digest
+const TOP_ENTRIES: usize = 5;
+
+/// The latest period finished by `now`, local days and weeks starting on Monday
+pub(crate) fn latest_period(
+    period: DigestPeriod,
+    now: DateTime<Utc>,
+    offset: FixedOffset,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    let today = now.with_timezone(&offset).date_naive();
+    let (end, days) = match period {
+        DigestPeriod::Daily => (today, Days::new(1)),
+        DigestPeriod::Weekly => (
+            today - Days::new(today.weekday().num_days_from_monday().into()),
+            Days::new(7),
+        ),
+    };
+    let start_of = |day: NaiveDate| {
+        day.and_time(NaiveTime::MIN)
+            .and_local_timezone(offset)
+            .single()
+            .expect("fixed offsets are never ambiguous")
+            .to_utc()
+    };
+    (start_of(end - days), start_of(end))
+}
+
+/// Ranking lines of the top entries, with their movement since the previous period
+fn top_lines<T: Copy + Eq + Hash + Mentionable>(
+    ranked: &[(T, u64, Activity)],
+    comparison: &Comparison<T>,
+) -> String {
+    let sum = ranked
+        .iter()
+        .map(|(_, count, _)| *count)
+        .sum::<u64>()
+        .max(1) as f64;
+    ranked
+        .iter()
+        .take(TOP_ENTRIES)
+        .enumerate()
+        .map(|(i, (id, count, activity))| {
+            let line = DeletedMessages::Include.line(
+                i + 1,
+                &id.mention().to_string(),
+                *count,
+                (*count * 100) as f64 / sum,
+                activity,
+            );
+            format!("{line} {}", comparison.delta(id, i + 1, *count))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Build the digest of `from..to` compared with the period of the same length before it, `None`
+/// when nobody sent a message
+pub(crate) async fn digest_embed(
+    db: &BotDatabase,
+    guild_id: GuildId,
+    guild_name: &str,
+    period: DigestPeriod,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Option<CreateEmbed>, BotError> {
+    let (previous_from, previous_to) = previous_window(from, to);
+    let rank = DeletedMessages::Include;
+    let channels = rank.rank(
+        db.message()
+            .get_channel_activity(guild_id, Some(from), Some(to))
+            .await?,
+    );
+    if channels.is_empty() {
+        return Ok(None);
+    }
+    let users = rank.rank(
+        db.message()
+            .get_user_activity(guild_id, None, Some(from), Some(to))
+            .await?,
+    );
+    let previous_channels = rank.rank(
+        db.message()
+            .get_channel_activity(guild_id, Some(previous_from), Some(previous_to))
+            .await?,
+    );
+    let previous_users = rank.rank(
+        db.message()
+            .get_user_activity(guild_id, None, Some(previous_from), Some(previous_to))
+            .await?,
+    );
+    let (previous_channel_count, previous_user_count) =
+        (previous_channels.len() as u64, previous_users.len() as u64);
+    let previous_channels = Comparison::new(&previous_channels);
+    let previous_users = Comparison::new(&previous_users);
+
+    let sum = channels.iter().map(|(_, count, _)| *count).sum::<u64>();
+    let embed = CreateEmbed::default()
+        .title(format!("{guild_name} {}活跃度摘要", period.name()))
+        .field(
+            "总条数",
+            format!("{sum} ({})", count_delta(sum, previous_channels.total())),
+            true,
+        )
+        .field(
+            "活跃频道",
+            format!(
+                "{} ({})",
+                channels.len(),
+                count_delta(channels.len() as u64, previous_channel_count)
+            ),
+            true,
+        )
+        .field(
+            "发言用户",
+            format!(
+                "{} ({})",
+                users.len(),
+                count_delta(users.len() as u64, previous_user_count)
+            ),
+            true,
+        )
+        .field(
+            "最活跃的频道",
+            top_lines(&channels, &previous_channels),
+            false,
+        )
+        .field("最活跃的用户", top_lines(&users, &previous_users), false)
+        .field(
+            "统计时间范围",
+            format!("<t:{}:f> - <t:{}:f>", from.timestamp(), to.timestamp()),
+            false,
+        )
+        .color(DARK_GREEN);
+    Ok(Some(embed))
+}
+
+#[command(slash_command, guild_only, ephemeral, check = "check_admin")]
+/// 设置定期发送活跃度摘要的频道
+pub async fn digest_set(
+    ctx: Context<'_>,
+    #[description = "发送摘要的频道"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+    #[description = "发送周期, 默认为每周"] period: Option<DigestPeriod>,
+) -> Result<(), BotError> {
+    let period = period.unwrap_or_default();
+    let guild_id = ctx.guild_id().expect("guild_only command");
+    if channel.guild_id != guild_id {
+        ctx.say("❌ **错误**\n\n摘要频道必须在当前服务器中。")
+            .await?;
+        return Ok(());
+    }
+    ctx.data().cfg.rcu(|cfg| {
+        let mut cfg = BotCfg::clone(cfg);
+        cfg.digests.insert(
+            guild_id,
+            DigestCfg {
+                channel_id: channel.id,
+                period,
+            },
+        );
+        cfg
+    });
+    if let Err(why) = ctx.data().cfg.load().write().await {
+        ctx.say(format!("❌ **错误**\n\n无法更新配置文件: {why:?}"))
+            .await?;
+        return Err(why);
+    }
+    // The period that just finished predates the digest, the first one is the next period
+    let (from, to) = latest_period(period, Utc::now(), local_offset(ctx)?);
+    ctx.data().db.digest().claim(guild_id, to).await?;
+    ctx.say(format!(
+        "✅ **成功**\n\n活跃度摘要将{}发送到 {}, 下一次发送时间为 <t:{}:f>。",
+        period.name(),
+        channel.mention(),
+        (to + (to - from)).timestamp()
+    ))
+    .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only, ephemeral, check = "check_admin")]
+/// 停止定期发送活跃度摘要
+pub async fn digest_disable(ctx: Context<'_>) -> Result<(), BotError> {
+    let guild_id = ctx.guild_id().expect("guild_only command");
+    if !ctx.data().cfg.load().digests.contains_key(&guild_id) {
+        ctx.say("❌ **错误**\n\n当前服务器没有设置活跃度摘要。")
+            .await?;
+        return Ok(());
+    }
+    ctx.data().cfg.rcu(|cfg| {
+        let mut cfg = BotCfg::clone(cfg);
+        cfg.digests.remove(&guild_id);
+        cfg
+    });
+    if let Err(why) = ctx.data().cfg.load().write().await {
+        ctx.say(format!("❌ **错误**\n\n无法更新配置文件: {why:?}"))
+            .await?;
+        return Err(why);
+    }
+    ctx.say("✅ **成功**\n\n已停止发送活跃度摘要。").await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_latest_period() {
+        let offset = FixedOffset::east_opt(8 * 3600).unwrap();
+        let at = |day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(2026, 10, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        // 2026-10-18 17:00 UTC is Monday 01:00 in UTC+8
+        assert_eq!(
+            latest_period(DigestPeriod::Daily, at(18, 17), offset),
+            (at(17, 16), at(18, 16))
+        );
+        assert_eq!(
+            latest_period(DigestPeriod::Weekly, at(18, 17), offset),
+            (at(11, 16), at(18, 16))
+        );
+        // Still Sunday locally, the week before is the latest one finished
+        assert_eq!(
+            latest_period(DigestPeriod::Weekly, at(18, 15), offset),
+            (at(4, 16), at(11, 16))
+        );
+    }
+}
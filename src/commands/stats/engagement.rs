@@ -0,0 +1,252 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use poise::{ChoiceParameter, CreateReply, command};
+use serenity::all::{colours::roles::DARK_GREEN, *};
+
+use super::{
+    super::{Context, check_admin},
+    channel_filter, description_lines, guild_choices, local_offset,
+};
+use crate::{error::BotError, repo::day_start};
+
+/// Periods listed in the breakdown, one more is read to tell the retention of the oldest
+const PERIODS: usize = 6;
+/// Whole UTC days the average DAU is taken over
+const AVERAGE_DAYS: u64 = 30;
+
+/// Length of the periods compared by the engagement breakdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+pub enum EngagementPeriod {
+    #[name = "日"]
+    Day,
+    #[default]
+    #[name = "周"]
+    Week,
+    #[name = "月 (30 天)"]
+    Month,
+}
+
+impl EngagementPeriod {
+    fn span(self) -> TimeDelta {
+        match self {
+            EngagementPeriod::Day => TimeDelta::days(1),
+            EngagementPeriod::Week => TimeDelta::weeks(1),
+            EngagementPeriod::Month => TimeDelta::days(30),
+        }
+    }
+
+    fn label(self, start: DateTime<Utc>, offset: FixedOffset) -> String {
+        let format = match self {
+            EngagementPeriod::Day => "%m-%d %H:%M",
+            EngagementPeriod::Week | EngagementPeriod::Month => "%m-%d",
+        };
+        start.with_timezone(&offset).format(format).to_string()
+    }
+}
+
+/// Chatters of one period of the breakdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PeriodRow {
+    start: DateTime<Utc>,
+    active: usize,
+    /// Active users whose first message falls in the period
+    new: usize,
+    /// Active users who were active in the period before too, and how many that period had
+    retained: Option<(usize, usize)>,
+}
+
+/// Break down the rolling periods ending at `end`, newest first.
+///
+/// `active[i]` holds the users active in the `i`-th period before `end`, the last one is only
+/// read for the retention of the one after it.
+fn period_rows(
+    end: DateTime<Utc>,
+    span: TimeDelta,
+    active: &[HashSet<UserId>],
+    first_seen: &HashMap<UserId, DateTime<Utc>>,
+) -> Vec<PeriodRow> {
+    active
+        .iter()
+        .enumerate()
+        .take(active.len().saturating_sub(1))
+        .map(|(i, users)| {
+            let start = end - span * (i as i32 + 1);
+            let new = users
+                .iter()
+                .filter(|user| first_seen.get(user).is_some_and(|first| *first >= start))
+                .count();
+            let previous = &active[i + 1];
+            PeriodRow {
+                start,
+                active: users.len(),
+                new,
+                retained: (!previous.is_empty())
+                    .then(|| (users.intersection(previous).count(), previous.len())),
+            }
+        })
+        .collect()
+}
+
+fn percentage(part: usize, whole: usize) -> f64 {
+    part as f64 * 100.0 / whole.max(1) as f64
+}
+
+#[command(slash_command, guild_only, ephemeral, check = "check_admin")]
+/// 查看活跃用户数 (DAU/WAU/MAU)、粘性、新增与留存发言用户
+pub async fn engagement(
+    ctx: Context<'_>,
+    #[description = "分期统计新增与留存的周期长度, 默认为周"] period: Option<EngagementPeriod>,
+    #[description = "指定服务器 ID, 默认为当前所在服务器"]
+    #[autocomplete = "guild_choices"]
+    guild: Option<Guild>,
+    #[description = "只统计该频道及其子频道"] channel: Option<GuildChannel>,
+    #[description = "是否为临时消息（仅自己可见）"] ephemeral: Option<bool>,
+) -> Result<(), BotError> {
+    let period = period.unwrap_or_default();
+    let ephemeral = ephemeral.unwrap_or(true);
+    if ephemeral {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
+    let guild_id = guild
+        .map(|g| g.id)
+        .or_else(|| ctx.guild_id())
+        .expect("Guild ID should be present in a guild context");
+    let guild_name = guild_id.name(ctx).unwrap_or_else(|| guild_id.to_string());
+    let channels = channel_filter(ctx, guild_id, channel.as_ref()).await?;
+    let channels = channels.as_deref();
+    let offset = local_offset(ctx)?;
+    let db = ctx.data().db.to_owned();
+    let now = Utc::now();
+
+    let active_since = async |days: i64| {
+        db.message()
+            .get_active_users(
+                guild_id,
+                channels,
+                Some(now - TimeDelta::days(days)),
+                None::<DateTime<Utc>>,
+            )
+            .await
+            .map(|users| users.len())
+    };
+    let (dau, wau, mau) = (
+        active_since(1).await?,
+        active_since(7).await?,
+        active_since(30).await?,
+    );
+    if mau == 0 {
+        ctx.say("近 30 天内没有发言记录。").await?;
+        return Ok(());
+    }
+    // Rollups are kept per UTC day, so the average is over whole UTC days
+    let today = day_start(now.date_naive());
+    let daily = db
+        .message()
+        .get_daily_active_users(
+            guild_id,
+            channels,
+            Some(today - TimeDelta::days(AVERAGE_DAYS as i64)),
+            Some(today),
+        )
+        .await?;
+    let average_dau =
+        daily.iter().map(|(_, count)| *count).sum::<u64>() as f64 / AVERAGE_DAYS as f64;
+
+    let span = period.span();
+    let mut active = Vec::with_capacity(PERIODS + 1);
+    for i in 0..=PERIODS as i32 {
+        active.push(
+            db.message()
+                .get_active_users(
+                    guild_id,
+                    channels,
+                    Some(now - span * (i + 1)),
+                    Some(now - span * i),
+                )
+                .await?,
+        );
+    }
+    let first_seen = db.message().first_seen_by_user(guild_id, channels).await?;
+    let rows = period_rows(now, span, &active, &first_seen);
+    let lines = rows.iter().map(|row| {
+        let retained = row.retained.map_or_else(
+            || "-".to_owned(),
+            |(retained, previous)| format!("{retained} ({:.1}%)", percentage(retained, previous)),
+        );
+        format!(
+            "`{}` 活跃 {} · 新增 {} · 留存 {retained}",
+            period.label(row.start, offset),
+            row.active,
+            row.new
+        )
+    });
+
+    let mut embed = CreateEmbed::default()
+        .title(format!("{guild_name} 用户活跃度"))
+        .description(description_lines(
+            std::iter::once(format!(
+                "每{}发言用户, 从最近一期开始, 留存为上一期也发言的用户:",
+                period.name()
+            ))
+            .chain(lines),
+        ))
+        .field("DAU (近 24 小时)", dau.to_string(), true)
+        .field("WAU (近 7 天)", wau.to_string(), true)
+        .field("MAU (近 30 天)", mau.to_string(), true)
+        .field(
+            format!("日均 DAU (近 {AVERAGE_DAYS} 个 UTC 日)"),
+            format!("{average_dau:.1}"),
+            true,
+        )
+        .field(
+            "粘性 (日均 DAU / MAU)",
+            format!("{:.1}%", average_dau * 100.0 / mau as f64),
+            true,
+        )
+        .color(DARK_GREEN);
+    if let Some(channel) = channel {
+        embed = embed.field("频道", channel.mention().to_string(), true);
+    }
+    ctx.send(CreateReply::default().embed(embed).ephemeral(ephemeral))
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_period_rows() {
+        let end = day_start(chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap());
+        let span = TimeDelta::weeks(1);
+        let users = |ids: &[u64]| ids.iter().map(|&id| UserId::new(id)).collect();
+        let active = [users(&[1, 2, 3]), users(&[1, 2]), HashSet::new()];
+        let first_seen = HashMap::from([
+            (UserId::new(1), end - TimeDelta::days(10)),
+            (UserId::new(2), end - TimeDelta::days(30)),
+            (UserId::new(3), end - TimeDelta::days(2)),
+        ]);
+        let rows = period_rows(end, span, &active, &first_seen);
+        assert_eq!(
+            rows,
+            [
+                PeriodRow {
+                    start: end - span,
+                    active: 3,
+                    new: 1,
+                    retained: Some((2, 2)),
+                },
+                PeriodRow {
+                    start: end - span * 2,
+                    active: 2,
+                    new: 1,
+                    retained: None,
+                },
+            ]
+        );
+    }
+}
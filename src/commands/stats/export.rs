@@ -0,0 +1,393 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{SecondsFormat, Utc};
+use poise::{ChoiceParameter, CreateReply, command};
+use serde::Serialize;
+use serenity::all::*;
+use snafu::ResultExt;
+use tracing::warn;
+
+use super::{
+    super::{Context, check_admin},
+    TimeExpr, channel_filter, channel_names, guild_choices, time_range, timestamp_choices,
+    user_names,
+};
+use crate::{
+    error::BotError,
+    repo::{Activity, ActivityBy},
+};
+
+/// Largest attachment a bot can upload to a guild without boosts
+const MAX_ATTACHMENT_SIZE: u64 = 10 * 1024 * 1024;
+/// Ranking entries read, named and written at a time
+const PAGE_SIZE: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+pub enum ExportKind {
+    #[default]
+    #[name = "频道"]
+    Channels,
+    #[name = "用户"]
+    Users,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+pub enum ExportFormat {
+    #[default]
+    #[name = "CSV"]
+    Csv,
+    #[name = "JSON"]
+    Json,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// The parameters an export was made with
+#[derive(Debug, Serialize)]
+struct ExportQuery {
+    kind: &'static str,
+    guild_id: String,
+    guild_name: String,
+    channel_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    exported_at: String,
+}
+
+impl ExportQuery {
+    /// The parameters as lines of the reply, a CSV file has nowhere to keep them
+    fn describe(&self) -> String {
+        [
+            (
+                "服务器",
+                Some(format!("{} ({})", self.guild_name, self.guild_id)),
+            ),
+            (
+                "频道",
+                self.channel_id.as_ref().map(|id| format!("<#{id}>")),
+            ),
+            ("开始", self.from.to_owned()),
+            ("结束", self.to.to_owned()),
+            ("导出时间", Some(self.exported_at.to_owned())),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some(format!("{key}: {}", value?)))
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+/// Ids are strings, JSON numbers lose the precision of snowflakes in most parsers
+#[derive(Debug, Serialize)]
+struct ExportRow {
+    rank: usize,
+    id: String,
+    name: String,
+    messages: u64,
+    deleted: u64,
+    percentage: f64,
+}
+
+/// Rows of a ranking page starting at `offset`, `sum` is the total of the whole ranking
+fn export_rows(
+    page: Vec<(u64, Activity)>,
+    offset: usize,
+    sum: u64,
+    names: &HashMap<u64, String>,
+) -> Vec<ExportRow> {
+    let sum = sum.max(1) as f64;
+    page.into_iter()
+        .enumerate()
+        .map(|(i, (id, activity))| ExportRow {
+            rank: offset + i + 1,
+            id: id.to_string(),
+            name: names.get(&id).cloned().unwrap_or_default(),
+            messages: activity.total,
+            deleted: activity.deleted,
+            percentage: activity.total as f64 * 100.0 / sum,
+        })
+        .collect()
+}
+
+/// Write the rows to `path` one at a time
+fn write_export(
+    path: &Path,
+    format: ExportFormat,
+    query: &ExportQuery,
+    rows: impl Iterator<Item = ExportRow>,
+) -> Result<(), BotError> {
+    let mut file = BufWriter::new(File::create(path)?);
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(file);
+            for row in rows {
+                writer
+                    .serialize(row)
+                    .whatever_context::<_, BotError>("Failed to write an export row")?;
+            }
+            writer.flush()?;
+        }
+        ExportFormat::Json => {
+            write!(file, "{{\"query\":")?;
+            serde_json::to_writer(&mut file, query)
+                .whatever_context::<_, BotError>("Failed to write the export query")?;
+            write!(file, ",\"rows\":[")?;
+            for (i, row) in rows.enumerate() {
+                if i > 0 {
+                    write!(file, ",")?;
+                }
+                serde_json::to_writer(&mut file, &row)
+                    .whatever_context::<_, BotError>("Failed to write an export row")?;
+            }
+            write!(file, "]}}")?;
+            file.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[command(slash_command, guild_only, ephemeral, check = "check_admin")]
+/// 导出完整的频道或用户活跃度统计
+pub async fn stats_export(
+    ctx: Context<'_>,
+    #[description = "导出频道或用户统计, 默认为频道"] kind: Option<ExportKind>,
+    #[description = "文件格式, 默认为 CSV"] format: Option<ExportFormat>,
+    #[description = "指定服务器 ID, 默认为当前所在服务器"]
+    #[autocomplete = "guild_choices"]
+    guild: Option<Guild>,
+    #[description = "只统计该频道及其子频道, 仅用于用户统计"] channel: Option<GuildChannel>,
+    #[description = "统计时间范围开始时间, 如 today、7d、2025-07-01..2025-07-15, 默认无限制"]
+    #[autocomplete = "timestamp_choices"]
+    from: Option<TimeExpr>,
+    #[description = "统计时间范围结束时间, 如 yesterday、2025-07-15, 默认为现在"]
+    #[autocomplete = "timestamp_choices"]
+    to: Option<TimeExpr>,
+) -> Result<(), BotError> {
+    let (from, to) = time_range(ctx, from, to)?;
+    let kind = kind.unwrap_or_default();
+    let format = format.unwrap_or_default();
+    ctx.defer_ephemeral().await?;
+    let guild_id = guild
+        .map(|g| g.id)
+        .or_else(|| ctx.guild_id())
+        .expect("Guild ID should be present in a guild context");
+    let guild_name = guild_id.name(ctx).unwrap_or_else(|| guild_id.to_string());
+    let db = ctx.data().db.to_owned();
+    let channels = match kind {
+        ExportKind::Channels => None,
+        ExportKind::Users => channel_filter(ctx, guild_id, channel.as_ref()).await?,
+    };
+    let sum = db
+        .message()
+        .get_activity_sum(guild_id, channels.as_deref(), from, to)
+        .await?;
+    if sum.total == 0 {
+        ctx.say("该时间范围内没有发言记录。").await?;
+        return Ok(());
+    }
+    let query = ExportQuery {
+        kind: kind.name(),
+        guild_id: guild_id.to_string(),
+        guild_name,
+        channel_id: channel
+            .filter(|_| kind == ExportKind::Users)
+            .map(|c| c.id.to_string()),
+        from: from.map(|f| f.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        to: to.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        exported_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let description = query.describe();
+    let file_name = format!(
+        "{}-stats-{guild_id}-{}.{}",
+        match kind {
+            ExportKind::Channels => "channel",
+            ExportKind::Users => "user",
+        },
+        Utc::now().format("%Y%m%d%H%M%S"),
+        format.extension()
+    );
+    let path: PathBuf = std::env::temp_dir().join(format!("{}-{file_name}", ctx.id()));
+    // Pages go to the writer as they are read, only one or two are held at a time
+    let (pages, mut received) = tokio::sync::mpsc::channel::<Vec<ExportRow>>(2);
+    let writer = {
+        let path = path.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let rows = std::iter::from_fn(|| received.blocking_recv()).flatten();
+            write_export(&path, format, &query, rows)
+        })
+    };
+    let by = match kind {
+        ExportKind::Channels => ActivityBy::Channel,
+        ExportKind::Users => ActivityBy::User,
+    };
+    let mut rows = 0;
+    let read = async {
+        loop {
+            let page = db
+                .message()
+                .get_activity_page(
+                    by,
+                    guild_id,
+                    channels.as_deref(),
+                    from,
+                    to,
+                    rows as u64,
+                    PAGE_SIZE,
+                )
+                .await?;
+            let ids = page.iter().map(|(id, _)| *id);
+            let names = match kind {
+                ExportKind::Channels => {
+                    let ids = ids.map(ChannelId::new).collect::<Vec<_>>();
+                    channel_names(ctx, guild_id, &ids)
+                        .await?
+                        .into_iter()
+                        .map(|(id, name)| (id.get(), name))
+                        .collect()
+                }
+                ExportKind::Users => {
+                    let ids = ids.map(UserId::new).collect::<Vec<_>>();
+                    user_names(ctx, guild_id, &ids)
+                        .await?
+                        .into_iter()
+                        .map(|(id, name)| (id.get(), name))
+                        .collect()
+                }
+            };
+            let len = page.len();
+            // The writer only hangs up when it failed, its error is reported below
+            if len == 0
+                || pages
+                    .send(export_rows(page, rows, sum.total, &names))
+                    .await
+                    .is_err()
+            {
+                return Ok::<_, BotError>(());
+            }
+            rows += len;
+            if len < PAGE_SIZE as usize {
+                return Ok(());
+            }
+        }
+    }
+    .await;
+    drop(pages);
+    let written = writer
+        .await
+        .whatever_context::<_, BotError>("Export task panicked")
+        .and_then(|written| read.and(written));
+    let result = async {
+        written?;
+        let size = tokio::fs::metadata(&path).await?.len();
+        if size > MAX_ATTACHMENT_SIZE {
+            ctx.say(format!(
+                "❌ 导出文件过大 ({:.1} MiB), 请缩小时间范围后重试。",
+                size as f64 / 1024.0 / 1024.0
+            ))
+            .await?;
+            return Ok(());
+        }
+        let mut attachment = CreateAttachment::path(&path).await?;
+        attachment.filename = file_name;
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "✅ 已导出 {rows} 条{}统计。\n{description}",
+                    kind.name()
+                ))
+                .attachment(attachment),
+        )
+        .await?;
+        Ok::<_, BotError>(())
+    }
+    .await;
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        warn!("Failed to remove export file {}: {e}", path.display());
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_export() {
+        let query = ExportQuery {
+            kind: "频道",
+            guild_id: "1".into(),
+            guild_name: "guild".into(),
+            channel_id: None,
+            from: Some("2026-10-01T00:00:00Z".into()),
+            to: None,
+            exported_at: "2026-10-18T00:00:00Z".into(),
+        };
+        let page = vec![
+            (
+                10,
+                Activity {
+                    total: 3,
+                    deleted: 1,
+                },
+            ),
+            (
+                20,
+                Activity {
+                    total: 1,
+                    deleted: 0,
+                },
+            ),
+        ];
+        let names = HashMap::from([(10, "general, chat".to_owned())]);
+        let dir = std::env::temp_dir();
+
+        let csv = dir.join("dc-bot-test-export.csv");
+        write_export(
+            &csv,
+            ExportFormat::Csv,
+            &query,
+            export_rows(page.clone(), 0, 4, &names).into_iter(),
+        )
+        .unwrap();
+        let csv_text = std::fs::read_to_string(&csv).unwrap();
+        std::fs::remove_file(&csv).unwrap();
+        let lines = csv_text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "rank,id,name,messages,deleted,percentage");
+        assert_eq!(lines[1], "1,10,\"general, chat\",3,1,75.0");
+        assert_eq!(lines[2], "2,20,,1,0,25.0");
+        assert_eq!(
+            query.describe(),
+            "服务器: guild (1)\n开始: 2026-10-01T00:00:00Z\n导出时间: 2026-10-18T00:00:00Z"
+        );
+
+        let json = dir.join("dc-bot-test-export.json");
+        // A later page keeps counting ranks from where the previous one stopped
+        write_export(
+            &json,
+            ExportFormat::Json,
+            &query,
+            export_rows(page, 1000, 4, &names).into_iter(),
+        )
+        .unwrap();
+        let value: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        std::fs::remove_file(&json).unwrap();
+        assert_eq!(value["query"]["from"], "2026-10-01T00:00:00Z");
+        assert_eq!(value["rows"][0]["id"], "10");
+        assert_eq!(value["rows"][1]["rank"], 1002);
+        assert_eq!(value["rows"][1]["percentage"], 25.0);
+    }
+}
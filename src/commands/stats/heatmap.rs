@@ -0,0 +1,254 @@
+use chrono::{DateTime, Datelike, FixedOffset, TimeDelta, Timelike, Utc};
+use plotters::{
+    coord::Shift,
+    prelude::{
+        BitMapBackend, ChartBuilder, DrawingArea, IntoSegmentedCoord, RGBColor, Rectangle,
+        SegmentValue,
+    },
+    style::Color as _,
+};
+use poise::{ChoiceParameter, CreateReply, command};
+use serenity::all::{colours::roles::DARK_GREEN, *};
+use snafu::ResultExt;
+
+use super::{
+    super::{Context, check_admin},
+    TimeExpr, channel_filter,
+    chart::{DrawResult, FONT, render_png},
+    guild_choices, local_offset, time_range, timestamp_choices,
+};
+use crate::error::BotError;
+
+const HEATMAP_SIZE: (u32, u32) = (1200, 420);
+const FILE_NAME: &str = "heatmap.png";
+const WEEKDAYS: [&str; 7] = ["一", "二", "三", "四", "五", "六", "日"];
+const WEEKDAY_LABELS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+/// Emoji blocks from no messages to the busiest hour
+const LEVELS: [&str; 5] = ["⬛", "🟦", "🟩", "🟨", "🟥"];
+
+/// Messages per local weekday, Monday first, and hour
+type Grid = [[u64; 24]; 7];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+pub enum HeatmapStyle {
+    #[default]
+    #[name = "图片"]
+    Image,
+    #[name = "表情方块"]
+    Emoji,
+}
+
+/// Sum hourly counts into the local weekday and hour they fall on
+fn weekly_grid(hourly: &[(DateTime<Utc>, u64)], offset: FixedOffset) -> Grid {
+    let mut grid = [[0; 24]; 7];
+    for &(time, count) in hourly {
+        let local = time.with_timezone(&offset);
+        grid[local.weekday().num_days_from_monday() as usize][local.hour() as usize] += count;
+    }
+    grid
+}
+
+fn grid_max(grid: &Grid) -> u64 {
+    grid.iter().flatten().copied().max().unwrap_or(0)
+}
+
+/// The level of `count` among [`LEVELS`], only an empty hour gets the lowest one
+fn level(count: u64, max: u64) -> usize {
+    if count == 0 {
+        return 0;
+    }
+    1 + ((count * (LEVELS.len() as u64 - 1) - 1) / max.max(1)) as usize
+}
+
+fn render_emoji(grid: &Grid) -> String {
+    let max = grid_max(grid);
+    grid.iter()
+        .zip(WEEKDAYS)
+        .map(|(hours, weekday)| {
+            let blocks = hours
+                .iter()
+                .map(|&count| LEVELS[level(count, max)])
+                .collect::<String>();
+            format!("`周{weekday}` {blocks}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn draw(root: &DrawingArea<BitMapBackend, Shift>, grid: &Grid) -> DrawResult {
+    let max = grid_max(grid).max(1);
+    let mut chart = ChartBuilder::on(root)
+        .margin(20)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d((0..23).into_segmented(), (0..6).into_segmented())?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_labels(24)
+        .y_labels(7)
+        .x_label_formatter(&|x: &SegmentValue<i32>| match x {
+            SegmentValue::CenterOf(hour) => hour.to_string(),
+            _ => String::new(),
+        })
+        .y_label_formatter(&|y: &SegmentValue<i32>| match y {
+            // Monday on top
+            SegmentValue::CenterOf(row) => usize::try_from(6 - row)
+                .ok()
+                .and_then(|i| WEEKDAY_LABELS.get(i))
+                .map_or_else(String::new, |label| label.to_string()),
+            _ => String::new(),
+        })
+        .label_style((FONT, 16))
+        .draw()?;
+    chart.draw_series(grid.iter().enumerate().flat_map(|(weekday, hours)| {
+        hours.iter().enumerate().map(move |(hour, &count)| {
+            let (hour, row) = (hour as i32, 6 - weekday as i32);
+            // From white to green as the hour gets busier
+            let shade = |full: u8| 255 - ((255 - full as u64) * count / max) as u8;
+            Rectangle::new(
+                [
+                    (SegmentValue::Exact(hour), SegmentValue::Exact(row)),
+                    (SegmentValue::Exact(hour + 1), SegmentValue::Exact(row + 1)),
+                ],
+                RGBColor(shade(0x1f), shade(0x8b), shade(0x4c)).filled(),
+            )
+        })
+    }))?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[command(slash_command, guild_only, ephemeral, check = "check_admin")]
+/// 按星期和小时统计消息活跃度热力图
+pub async fn activity_heatmap(
+    ctx: Context<'_>,
+    #[description = "显示方式, 默认为图片"] style: Option<HeatmapStyle>,
+    #[description = "指定服务器 ID, 默认为当前所在服务器"]
+    #[autocomplete = "guild_choices"]
+    guild: Option<Guild>,
+    #[description = "只统计该频道及其子频道"] channel: Option<GuildChannel>,
+    #[description = "只统计该用户"] user: Option<User>,
+    #[description = "统计时间范围开始时间, 如 today、7d、2025-07-01..2025-07-15, 默认为四周前"]
+    #[autocomplete = "timestamp_choices"]
+    from: Option<TimeExpr>,
+    #[description = "统计时间范围结束时间, 如 yesterday、2025-07-15, 默认为现在"]
+    #[autocomplete = "timestamp_choices"]
+    to: Option<TimeExpr>,
+    #[description = "是否为临时消息（仅自己可见）"] ephemeral: Option<bool>,
+) -> Result<(), BotError> {
+    let (from, to) = time_range(ctx, from, to)?;
+    let style = style.unwrap_or_default();
+    let ephemeral = ephemeral.unwrap_or(true);
+    let to = to.unwrap_or_else(Utc::now);
+    let from = from.unwrap_or(to - TimeDelta::weeks(4));
+    if from >= to {
+        ctx.say("❌ 开始时间必须早于结束时间。").await?;
+        return Ok(());
+    }
+    if ephemeral {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
+    let guild_id = guild
+        .map(|g| g.id)
+        .or_else(|| ctx.guild_id())
+        .expect("Guild ID should be present in a guild context");
+    let guild_name = guild_id.name(ctx).unwrap_or_else(|| guild_id.to_string());
+    let channels = channel_filter(ctx, guild_id, channel.as_ref()).await?;
+    // Rollups only keep daily counts, so pruned days are left out
+    let hourly = ctx
+        .data()
+        .db
+        .message()
+        .get_raw_hourly_activity(
+            guild_id,
+            channels.as_deref(),
+            user.as_ref().map(|u| u.id),
+            Some(from),
+            Some(to),
+        )
+        .await?;
+    let grid = weekly_grid(&hourly, local_offset(ctx)?);
+    let sum = grid.iter().flatten().sum::<u64>();
+    if sum == 0 {
+        ctx.say("该时间范围内没有发言记录。").await?;
+        return Ok(());
+    }
+    let (peak_weekday, peak_hour) = (0..7)
+        .flat_map(|weekday| (0..24).map(move |hour| (weekday, hour)))
+        .max_by_key(|&(weekday, hour)| grid[weekday][hour])
+        .expect("grid is not empty");
+
+    let mut embed = CreateEmbed::default()
+        .title(format!("{guild_name} 活跃时段热力图"))
+        .field("总条数", sum.to_string(), true)
+        .field(
+            "最活跃时段",
+            format!(
+                "周{} {peak_hour:02}:00 ({} 条)",
+                WEEKDAYS[peak_weekday], grid[peak_weekday][peak_hour]
+            ),
+            true,
+        )
+        .field(
+            "统计时间范围",
+            format!("<t:{}:f> - <t:{}:f>", from.timestamp(), to.timestamp()),
+            false,
+        )
+        .color(DARK_GREEN);
+    if let Some(channel) = channel {
+        embed = embed.field("频道", channel.mention().to_string(), true);
+    }
+    if let Some(user) = user {
+        embed = embed.field("用户", user.mention().to_string(), true);
+    }
+    let mut reply = CreateReply::default().ephemeral(ephemeral);
+    match style {
+        HeatmapStyle::Image => {
+            let png = tokio::task::spawn_blocking(move || {
+                render_png(HEATMAP_SIZE, |root| draw(root, &grid))
+            })
+            .await
+            .whatever_context::<_, BotError>("Heatmap rendering panicked")??;
+            embed = embed.image(format!("attachment://{FILE_NAME}"));
+            reply = reply.attachment(CreateAttachment::bytes(png, FILE_NAME));
+        }
+        HeatmapStyle::Emoji => {
+            embed = embed.description(format!(
+                "每格为一小时, 从 0 时到 23 时, 颜色由少到多: {}\n\n{}",
+                LEVELS.concat(),
+                render_emoji(&grid)
+            ));
+        }
+    }
+    ctx.send(reply.embed(embed)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_weekly_grid() {
+        // 2026-10-18 is a Sunday, 17:00 UTC is Monday 01:00 in UTC+8
+        let at = |hour: u32| {
+            chrono::NaiveDate::from_ymd_opt(2026, 10, 18)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        let offset = FixedOffset::east_opt(8 * 3600).unwrap();
+        let grid = weekly_grid(&[(at(3), 2), (at(17), 5)], offset);
+        assert_eq!(grid[6][11], 2);
+        assert_eq!(grid[0][1], 5);
+        assert_eq!(grid.iter().flatten().sum::<u64>(), 7);
+
+        assert_eq!(level(0, 5), 0);
+        assert_eq!(level(1, 5), 1);
+        assert_eq!(level(5, 5), LEVELS.len() - 1);
+    }
+}
@@ -0,0 +1,766 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{StreamExt, stream};
+use poise::{ChoiceParameter, CreateReply};
+use serenity::all::{colours::roles::DARK_GREEN, *};
+use tracing::warn;
+
+use super::{
+    super::Context,
+    DeletedMessages, channel_names,
+    compare::{Comparison, count_delta, gone_text, previous_window},
+    description_lines,
+    members::{MemberFilter, Members},
+    tree::{ChannelGrouping, ChannelTree},
+    user_names,
+};
+use crate::{error::BotError, repo::Activity};
+
+/// Components stop responding after this long without a press
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+/// Most categories on a page of the tree view, each one brings its channels and threads along
+const TREE_PAGE_SIZE: usize = 5;
+/// Channels listed under each category of the tree view
+const TREE_CHANNELS: usize = 5;
+/// Threads and forum posts listed under each channel of the tree view
+const TREE_THREADS: usize = 3;
+
+/// Which ranking a leaderboard shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum View {
+    Channels,
+    Users,
+    /// Categories, with their most active channels and threads
+    Tree,
+}
+
+impl View {
+    const ALL: [View; 3] = [View::Channels, View::Users, View::Tree];
+
+    fn value(self) -> &'static str {
+        match self {
+            View::Channels => "channels",
+            View::Users => "users",
+            View::Tree => "tree",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            View::Channels => "频道排行",
+            View::Users => "用户排行",
+            View::Tree => "分类树",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            View::Channels => "频道活跃度统计",
+            View::Users => "用户活跃度统计",
+            View::Tree => "频道分类树",
+        }
+    }
+
+    fn gone_title(self) -> &'static str {
+        match self {
+            View::Channels => "不再活跃的频道",
+            View::Users => "不再活跃的用户",
+            View::Tree => "不再活跃的分类",
+        }
+    }
+}
+
+/// The filters a leaderboard was asked for, shared by both of its views
+pub(super) struct LeaderboardQuery {
+    pub guild_id: GuildId,
+    pub guild_name: String,
+    /// Only count this channel and everything under it
+    pub channel: Option<GuildChannel>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub deleted: DeletedMessages,
+    /// Compare with the window of the same length before `from`
+    pub compare: bool,
+    /// Only count the users passing this filter
+    pub members: MemberFilter,
+    /// What the channel view sums the activity of threads and channels into
+    pub grouping: ChannelGrouping,
+}
+
+/// A loaded ranking, most active first
+struct Ranking<T> {
+    entries: Vec<(T, u64, Activity)>,
+    comparison: Option<Comparison<T>>,
+    db_duration: Duration,
+}
+
+impl<T: Copy + Eq + Hash> Ranking<T> {
+    fn sum(&self) -> u64 {
+        self.entries.iter().map(|(_, count, _)| *count).sum()
+    }
+
+    fn position(&self, id: T) -> Option<usize> {
+        self.entries.iter().position(|(entry, ..)| *entry == id)
+    }
+}
+
+/// Sum, previous sum, query time and the entries gone since the previous window of a ranking
+fn summary<T: Copy + Eq + Hash + Mentionable>(
+    ranking: Option<&Ranking<T>>,
+) -> (u64, Option<u64>, Duration, Option<String>) {
+    let Some(ranking) = ranking else {
+        return (0, None, Duration::ZERO, None);
+    };
+    let comparison = ranking.comparison.as_ref();
+    let gone = comparison
+        .map(|c| c.gone(ranking.entries.iter().map(|(id, ..)| id)))
+        .filter(|(_, count)| *count > 0)
+        .map(|(gone, count)| gone_text(gone, count));
+    (
+        ranking.sum(),
+        comparison.map(|c| c.total()),
+        ranking.db_duration,
+        gone,
+    )
+}
+
+/// Ids of the entries on the page starting at `start`
+fn page_ids<T: Copy>(ranking: &Ranking<T>, start: usize, page_size: usize) -> Vec<T> {
+    ranking
+        .entries
+        .iter()
+        .skip(start)
+        .take(page_size)
+        .map(|(id, ..)| *id)
+        .collect()
+}
+
+/// Pages needed for `len` entries, an empty ranking still has its one page
+fn page_count(len: usize, page_size: usize) -> usize {
+    len.div_ceil(page_size).max(1)
+}
+
+/// A ranking of channels or users shown one page at a time, each view is loaded when first shown
+struct Leaderboard {
+    query: LeaderboardQuery,
+    /// The channel filter resolved to every channel under it
+    channel_ids: Option<Vec<ChannelId>>,
+    previous: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// The member filter resolved, `None` when it keeps everyone
+    members: Option<Members>,
+    /// Loaded along with the first view that needs it
+    tree: Option<ChannelTree>,
+    channels: Option<Ranking<ChannelId>>,
+    users: Option<Ranking<UserId>>,
+    categories: Option<Ranking<ChannelId>>,
+    /// Ranked channels of each category and threads of each channel, for the tree view
+    children: HashMap<ChannelId, Vec<(ChannelId, u64, Activity)>>,
+    view: View,
+    page: usize,
+    page_size: usize,
+}
+
+impl Leaderboard {
+    fn len(&self) -> usize {
+        match self.view {
+            View::Channels => self.channels.as_ref().map_or(0, |r| r.entries.len()),
+            View::Users => self.users.as_ref().map_or(0, |r| r.entries.len()),
+            View::Tree => self.categories.as_ref().map_or(0, |r| r.entries.len()),
+        }
+    }
+
+    fn page_size(&self) -> usize {
+        match self.view {
+            View::Tree => self.page_size.min(TREE_PAGE_SIZE),
+            View::Channels | View::Users => self.page_size,
+        }
+    }
+
+    fn pages(&self) -> usize {
+        page_count(self.len(), self.page_size())
+    }
+
+    /// Show `view` from its first page, loading it if needed
+    async fn switch(&mut self, ctx: Context<'_>, view: View) -> Result<(), BotError> {
+        if view != self.view {
+            self.view = view;
+            self.page = 0;
+        }
+        let needs_tree = view == View::Tree
+            || (view == View::Channels && self.query.grouping != ChannelGrouping::None);
+        if needs_tree && self.tree.is_none() {
+            self.tree = Some(ChannelTree::load(ctx, self.query.guild_id).await?);
+        }
+        let db = ctx.data().db.to_owned();
+        let q = &self.query;
+        let members = self.members.as_ref();
+        let keep = self
+            .channel_ids
+            .as_ref()
+            .map(|ids| ids.iter().copied().collect::<HashSet<_>>());
+        let channel_activity = async |from, to| {
+            let mut entries = match members {
+                Some(members) => {
+                    db.message()
+                        .get_channel_activity_of(q.guild_id, |id| members.allows(id), from, to)
+                        .await?
+                }
+                None => {
+                    db.message()
+                        .get_channel_activity(q.guild_id, from, to)
+                        .await?
+                }
+            };
+            entries.retain(|(id, _)| keep.as_ref().is_none_or(|keep| keep.contains(id)));
+            Ok::<_, BotError>(entries)
+        };
+        let grouped = |tree: Option<&ChannelTree>,
+                       entries: Vec<(ChannelId, Activity)>,
+                       grouping| match tree {
+            Some(tree) => q.deleted.rank(tree.roll_up(&entries, grouping)),
+            None => q.deleted.rank(entries),
+        };
+        let in_members = |(id, _): &(UserId, Activity)| members.is_none_or(|m| m.allows(*id));
+        match view {
+            View::Channels if self.channels.is_none() => {
+                let start = Instant::now();
+                let entries = channel_activity(q.from, q.to).await?;
+                let previous = match self.previous {
+                    Some((from, to)) => Some(channel_activity(Some(from), Some(to)).await?),
+                    None => None,
+                };
+                let tree = resolved(ctx, &mut self.tree, &entries, previous.as_deref()).await?;
+                self.channels = Some(Ranking {
+                    comparison: previous
+                        .map(|previous| Comparison::new(&grouped(tree, previous, q.grouping))),
+                    entries: grouped(tree, entries, q.grouping),
+                    db_duration: start.elapsed(),
+                });
+            }
+            View::Tree if self.categories.is_none() => {
+                let start = Instant::now();
+                let entries = channel_activity(q.from, q.to).await?;
+                let previous = match self.previous {
+                    Some((from, to)) => Some(channel_activity(Some(from), Some(to)).await?),
+                    None => None,
+                };
+                let tree = resolved(ctx, &mut self.tree, &entries, previous.as_deref()).await?;
+                self.children = tree
+                    .map(|tree| tree.children(&entries))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(id, children)| (id, q.deleted.rank(children)))
+                    .collect();
+                self.categories = Some(Ranking {
+                    comparison: previous.map(|previous| {
+                        Comparison::new(&grouped(tree, previous, ChannelGrouping::Category))
+                    }),
+                    entries: grouped(tree, entries, ChannelGrouping::Category),
+                    db_duration: start.elapsed(),
+                });
+            }
+            View::Users if self.users.is_none() => {
+                let start = Instant::now();
+                let channel_ids = self.channel_ids.as_deref();
+                let mut entries = db
+                    .message()
+                    .get_user_activity(q.guild_id, channel_ids, q.from, q.to)
+                    .await?;
+                entries.retain(in_members);
+                let comparison = match self.previous {
+                    Some((from, to)) => {
+                        let mut previous = db
+                            .message()
+                            .get_user_activity(q.guild_id, channel_ids, Some(from), Some(to))
+                            .await?;
+                        previous.retain(in_members);
+                        Some(Comparison::new(&q.deleted.rank(previous)))
+                    }
+                    None => None,
+                };
+                self.users = Some(Ranking {
+                    entries: q.deleted.rank(entries),
+                    comparison,
+                    db_duration: start.elapsed(),
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Ranking lines of the current page, `viewer` is pointed out in the user view and the tree
+    /// view lists what is under each entry.
+    ///
+    /// Names come from the known names and the cache, only channels missing from both are
+    /// fetched.
+    async fn page_lines(&self, ctx: Context<'_>, viewer: UserId) -> Result<Vec<String>, BotError> {
+        let start = self.page * self.page_size();
+        let guild_id = self.query.guild_id;
+        match self.view {
+            View::Channels => {
+                let Some(ranking) = &self.channels else {
+                    return Ok(Vec::new());
+                };
+                let ids = page_ids(ranking, start, self.page_size());
+                let names = channel_labels(ctx, guild_id, &ids).await?;
+                Ok(self.lines(ranking, start, names, |_| false))
+            }
+            View::Tree => {
+                let (Some(ranking), Some(tree)) = (&self.categories, &self.tree) else {
+                    return Ok(Vec::new());
+                };
+                let ids = page_ids(ranking, start, self.page_size());
+                let subtrees = ids
+                    .iter()
+                    .map(|id| self.subtree(tree, *id, 1))
+                    .collect::<Vec<_>>();
+                let all_ids = ids
+                    .iter()
+                    .copied()
+                    .chain(subtrees.iter().flatten().map(|(_, id, _)| *id))
+                    .collect::<Vec<_>>();
+                let mut names = channel_labels(ctx, guild_id, &all_ids).await?;
+                let mut child_names = names.split_off(ids.len()).into_iter();
+                let lines = self
+                    .lines(ranking, start, names, |_| false)
+                    .into_iter()
+                    .zip(subtrees)
+                    .flat_map(|(line, subtree)| {
+                        let children = subtree
+                            .into_iter()
+                            .zip(child_names.by_ref())
+                            .map(|((depth, _, count), name)| {
+                                format!("{}└ {count} - {name}", "\u{3000}".repeat(depth))
+                            })
+                            .collect::<Vec<_>>();
+                        std::iter::once(line).chain(children)
+                    })
+                    .collect();
+                Ok(lines)
+            }
+            View::Users => {
+                let Some(ranking) = &self.users else {
+                    return Ok(Vec::new());
+                };
+                let ids = page_ids(ranking, start, self.page_size());
+                let mut known = user_names(ctx, guild_id, &ids).await?;
+                let names = ids
+                    .iter()
+                    .map(|user_id| {
+                        known
+                            .remove(user_id)
+                            .unwrap_or_else(|| user_id.mention().to_string())
+                    })
+                    .collect();
+                Ok(self.lines(ranking, start, names, |id| *id == viewer))
+            }
+        }
+    }
+
+    /// The most active channels of a category or threads of a channel, each with its own, and
+    /// how deep they sit under the page entry
+    fn subtree(
+        &self,
+        tree: &ChannelTree,
+        id: ChannelId,
+        depth: usize,
+    ) -> Vec<(usize, ChannelId, u64)> {
+        let limit = if tree.is_category(id) {
+            TREE_CHANNELS
+        } else {
+            TREE_THREADS
+        };
+        let mut nodes = Vec::new();
+        for (child, count, _) in self.children.get(&id).into_iter().flatten().take(limit) {
+            nodes.push((depth, *child, *count));
+            nodes.extend(self.subtree(tree, *child, depth + 1));
+        }
+        nodes
+    }
+
+    fn lines<T: Copy + Eq + Hash>(
+        &self,
+        ranking: &Ranking<T>,
+        start: usize,
+        names: Vec<String>,
+        highlight: impl Fn(&T) -> bool,
+    ) -> Vec<String> {
+        let sum = ranking.sum().max(1) as f64;
+        ranking
+            .entries
+            .iter()
+            .skip(start)
+            .zip(names)
+            .enumerate()
+            .map(|(i, ((id, count, activity), name))| {
+                let rank = start + i + 1;
+                let line = self.query.deleted.line(
+                    rank,
+                    &name,
+                    *count,
+                    (count * 100) as f64 / sum,
+                    activity,
+                );
+                let line = match &ranking.comparison {
+                    Some(c) => format!("{line} {}", c.delta(id, rank, *count)),
+                    None => line,
+                };
+                if highlight(id) {
+                    format!("👉 **{line}**")
+                } else {
+                    line
+                }
+            })
+            .collect()
+    }
+
+    async fn embed(&self, ctx: Context<'_>, viewer: UserId) -> Result<CreateEmbed, BotError> {
+        let q = &self.query;
+        let start = Instant::now();
+        let lines = self.page_lines(ctx, viewer).await?;
+        let network_duration = start.elapsed();
+        let (sum, previous_total, db_duration, gone) = match self.view {
+            View::Channels => summary(self.channels.as_ref()),
+            View::Users => summary(self.users.as_ref()),
+            View::Tree => summary(self.categories.as_ref()),
+        };
+        let mut embed = CreateEmbed::default()
+            .title(format!("{} {}", q.guild_name, self.view.title()))
+            .description(if lines.is_empty() {
+                "该时间范围内没有发言记录。".to_owned()
+            } else {
+                description_lines(lines)
+            })
+            .field(
+                if q.deleted == DeletedMessages::Rate {
+                    "已删除条数"
+                } else {
+                    "总条数"
+                },
+                match previous_total {
+                    Some(previous) => format!("{sum} ({})", count_delta(sum, previous)),
+                    None => sum.to_string(),
+                },
+                false,
+            )
+            .field(
+                "频道",
+                q.channel
+                    .as_ref()
+                    .map(|c| c.mention().to_string())
+                    .unwrap_or_else(|| "所有频道".into()),
+                false,
+            )
+            .field(
+                "数据库查询耗时",
+                format!("{}ms", db_duration.as_millis()),
+                true,
+            )
+            .field(
+                "网络请求耗时",
+                format!("{}ms", network_duration.as_millis()),
+                true,
+            )
+            .field(
+                "统计时间范围",
+                format!(
+                    "{} - {}",
+                    q.from.map_or_else(
+                        || "不限".into(),
+                        |f| f.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+                    ),
+                    q.to.map_or_else(
+                        || "不限".into(),
+                        |t| t.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+                    )
+                ),
+                false,
+            )
+            .footer(CreateEmbedFooter::new(format!(
+                "第 {} / {} 页 · 共 {} 项",
+                self.page + 1,
+                self.pages(),
+                self.len()
+            )))
+            .color(DARK_GREEN);
+        if self.view == View::Channels && q.grouping != ChannelGrouping::None {
+            embed = embed.field("合并方式", q.grouping.name(), true);
+        }
+        if let Some(members) = &self.members {
+            embed = embed.field("成员筛选", members.filter().describe(), false);
+        }
+        if let Some((previous_from, previous_to)) = self.previous {
+            embed = embed.field(
+                "对比时间范围",
+                format!(
+                    "<t:{}:f> - <t:{}:f>",
+                    previous_from.timestamp(),
+                    previous_to.timestamp()
+                ),
+                false,
+            );
+        }
+        if let Some(gone) = gone {
+            embed = embed.field(self.view.gone_title(), gone, false);
+        }
+        Ok(embed)
+    }
+
+    fn components(&self, prefix: &str, disabled: bool) -> Vec<CreateActionRow> {
+        let options = View::ALL
+            .into_iter()
+            .map(|view| {
+                CreateSelectMenuOption::new(view.label(), view.value())
+                    .default_selection(view == self.view)
+            })
+            .collect();
+        let select = CreateSelectMenu::new(
+            format!("{prefix}view"),
+            CreateSelectMenuKind::String { options },
+        )
+        .disabled(disabled);
+        let buttons = vec![
+            CreateButton::new(format!("{prefix}prev"))
+                .label("上一页")
+                .emoji('◀')
+                .style(ButtonStyle::Secondary)
+                .disabled(disabled || self.page == 0),
+            CreateButton::new(format!("{prefix}next"))
+                .label("下一页")
+                .emoji('▶')
+                .style(ButtonStyle::Secondary)
+                .disabled(disabled || self.page + 1 >= self.pages()),
+            CreateButton::new(format!("{prefix}me"))
+                .label("我的排名")
+                .emoji('🎯')
+                .style(ButtonStyle::Primary)
+                .disabled(disabled),
+        ];
+        vec![
+            CreateActionRow::SelectMenu(select),
+            CreateActionRow::Buttons(buttons),
+        ]
+    }
+}
+
+/// Names of channels in order, only the ones neither known nor cached are fetched
+async fn channel_labels(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    channel_ids: &[ChannelId],
+) -> Result<Vec<String>, BotError> {
+    let known = channel_names(ctx, guild_id, channel_ids).await?;
+    Ok(channel_ids
+        .iter()
+        .map(async |channel_id| match known.get(channel_id) {
+            Some(name) => name.to_owned(),
+            None => channel_id
+                .name(ctx)
+                .await
+                .unwrap_or_else(|_| channel_id.to_string()),
+        })
+        .collect::<stream::FuturesOrdered<_>>()
+        .collect::<Vec<_>>()
+        .await)
+}
+
+/// Reply with the leaderboard and keep serving its buttons to the invoking user until it has been
+/// idle for a while, then disable them
+pub(super) async fn leaderboard(
+    ctx: Context<'_>,
+    query: LeaderboardQuery,
+    channel_ids: Option<Vec<ChannelId>>,
+    view: View,
+    page_size: usize,
+    ephemeral: bool,
+) -> Result<(), BotError> {
+    let previous = query
+        .from
+        .filter(|_| query.compare)
+        .map(|from| previous_window(from, query.to.unwrap_or_else(Utc::now)));
+    let members = if query.members.is_empty() {
+        None
+    } else {
+        Some(Members::resolve(ctx, query.guild_id, query.members).await?)
+    };
+    let mut board = Leaderboard {
+        query,
+        channel_ids,
+        previous,
+        members,
+        tree: None,
+        channels: None,
+        users: None,
+        categories: None,
+        children: HashMap::new(),
+        view,
+        page: 0,
+        page_size,
+    };
+    board.switch(ctx, view).await?;
+    if board.len() == 0 {
+        ctx.send(
+            CreateReply::default()
+                .content("该服务器今天还没有发言记录。")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let prefix = format!("{}-leaderboard-", ctx.id());
+    let mut embed = board.embed(ctx, ctx.author().id).await?;
+    let handle = ctx
+        .send(
+            CreateReply::default()
+                .embed(embed.to_owned())
+                .components(board.components(&prefix, false))
+                .ephemeral(ephemeral),
+        )
+        .await?;
+    // Each press is answered through its own token, the original one expires after 15 minutes
+    let mut last_press = None::<ComponentInteraction>;
+    while let Some(press) = ComponentInteractionCollector::new(ctx)
+        .filter({
+            let prefix = prefix.to_owned();
+            move |press| press.data.custom_id.starts_with(&prefix)
+        })
+        .timeout(IDLE_TIMEOUT)
+        .await
+    {
+        if press.user.id != ctx.author().id {
+            // Someone else's leaderboard, point them to their own instead of taking it over
+            if let Err(e) = press
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("❌ 只有使用命令的人可以操作该排行榜, 请自己使用该命令查看。")
+                            .ephemeral(true),
+                    ),
+                )
+                .await
+            {
+                warn!("Failed to answer leaderboard press: {e}");
+            }
+            continue;
+        }
+        if let Err(e) = press.defer(ctx).await {
+            warn!("Failed to acknowledge leaderboard press: {e}");
+            continue;
+        }
+        // A failed press leaves the board as it was, the buttons still get disabled in the end
+        let update = async {
+            let viewer = press.user.id;
+            match (&press.data.custom_id[prefix.len()..], &press.data.kind) {
+                ("view", ComponentInteractionDataKind::StringSelect { values }) => {
+                    if let Some(view) = View::ALL
+                        .into_iter()
+                        .find(|view| values.first().is_some_and(|v| v == view.value()))
+                    {
+                        board.switch(ctx, view).await?;
+                    }
+                }
+                ("prev", _) => board.page = board.page.saturating_sub(1),
+                ("next", _) => board.page = (board.page + 1).min(board.pages() - 1),
+                ("me", _) => {
+                    board.switch(ctx, View::Users).await?;
+                    match board.users.as_ref().and_then(|r| r.position(viewer)) {
+                        Some(index) => board.page = index / board.page_size(),
+                        None => {
+                            press
+                                .create_followup(
+                                    ctx,
+                                    CreateInteractionResponseFollowup::new()
+                                        .content("❌ 你不在该排行榜中。")
+                                        .ephemeral(true),
+                                )
+                                .await?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            embed = board.embed(ctx, viewer).await?;
+            press
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .embed(embed.to_owned())
+                        .components(board.components(&prefix, false)),
+                )
+                .await?;
+            Ok::<_, BotError>(())
+        };
+        if let Err(e) = update.await {
+            warn!("Failed to update leaderboard: {e}");
+        }
+        last_press = Some(press);
+    }
+
+    let components = board.components(&prefix, true);
+    match last_press {
+        Some(press) => {
+            press
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .embed(embed)
+                        .components(components),
+                )
+                .await?;
+        }
+        None => {
+            handle
+                .edit(
+                    ctx,
+                    CreateReply::default().embed(embed).components(components),
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// The channel tree, if loaded, after looking up the channels of `entries` and `previous` it does
+/// not know yet
+async fn resolved<'a>(
+    ctx: Context<'_>,
+    tree: &'a mut Option<ChannelTree>,
+    entries: &[(ChannelId, Activity)],
+    previous: Option<&[(ChannelId, Activity)]>,
+) -> Result<Option<&'a ChannelTree>, BotError> {
+    if let Some(tree) = tree.as_mut() {
+        let ids = entries.iter().chain(previous.unwrap_or_default());
+        tree.resolve(ctx, ids.map(|(id, _)| *id)).await?;
+    }
+    Ok(tree.as_ref())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_page_count() {
+        assert_eq!(page_count(0, 20), 1);
+        assert_eq!(page_count(20, 20), 1);
+        assert_eq!(page_count(21, 20), 2);
+        let ranking = Ranking {
+            entries: [3, 1, 2]
+                .map(|id| (UserId::new(id), 1, Activity::default()))
+                .to_vec(),
+            comparison: None,
+            db_duration: Duration::ZERO,
+        };
+        assert_eq!(ranking.position(UserId::new(2)), Some(2));
+        assert_eq!(ranking.position(UserId::new(4)), None);
+        assert_eq!(ranking.sum(), 3);
+    }
+}
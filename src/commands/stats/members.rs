@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use moka::sync::Cache;
+use serenity::{all::*, collector::collect};
+use snafu::OptionExt;
+
+use super::super::Context;
+use crate::error::BotError;
+
+/// How long the members requested from the gateway are reused before being requested again
+const FETCHED_LIFETIME: Duration = Duration::from_secs(10 * 60);
+/// Longest wait for the next chunk of members
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(30);
+
+type RoleMap = Arc<HashMap<UserId, Vec<RoleId>>>;
+
+/// Roles of the members of the guilds whose cache is incomplete, by guild
+static FETCHED: LazyLock<Cache<GuildId, RoleMap>> = LazyLock::new(|| {
+    Cache::builder()
+        .max_capacity(16)
+        .time_to_live(FETCHED_LIFETIME)
+        .build()
+});
+
+/// Which users count towards a ranking, by their current membership and roles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct MemberFilter {
+    /// Only count members with this role
+    pub role: Option<RoleId>,
+    /// Leave out members with this role
+    pub exclude_role: Option<RoleId>,
+    /// Leave out users who are no longer members
+    pub members_only: bool,
+}
+
+impl MemberFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether a user passes the filter, `roles` is `None` when they are not a member
+    fn allows(&self, roles: Option<&[RoleId]>) -> bool {
+        match roles {
+            None => !self.members_only && self.role.is_none(),
+            Some(roles) => {
+                self.role.is_none_or(|role| roles.contains(&role))
+                    && self.exclude_role.is_none_or(|role| !roles.contains(&role))
+            }
+        }
+    }
+
+    /// The filter as shown in an embed
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(role) = self.role {
+            parts.push(format!("身份组 {}", role.mention()));
+        }
+        if let Some(role) = self.exclude_role {
+            parts.push(format!("排除 {}", role.mention()));
+        }
+        if self.members_only {
+            parts.push("仅当前成员".to_owned());
+        }
+        parts.join(" · ")
+    }
+}
+
+/// A [`MemberFilter`] resolved against the current members of a guild
+pub(super) struct Members {
+    filter: MemberFilter,
+    roles: RoleMap,
+}
+
+impl Members {
+    /// Read the members from the cache, or request them in chunks over the gateway when the cache
+    /// does not hold all of them and they were not requested in the last [`FETCHED_LIFETIME`]
+    pub async fn resolve(
+        ctx: Context<'_>,
+        guild_id: GuildId,
+        filter: MemberFilter,
+    ) -> Result<Self, BotError> {
+        let cached = ctx.cache().guild(guild_id).and_then(|guild| {
+            (guild.members.len() as u64 >= guild.member_count).then(|| {
+                guild
+                    .members
+                    .values()
+                    .map(|m| (m.user.id, m.roles.to_owned()))
+                    .collect()
+            })
+        });
+        if let Some(roles) = cached {
+            return Ok(Self {
+                filter,
+                roles: Arc::new(roles),
+            });
+        }
+        if let Some(roles) = FETCHED.get(&guild_id) {
+            return Ok(Self { filter, roles });
+        }
+        // Listen before asking, the first chunk may arrive right away. The chunks fill the cache
+        // and the known names on their way to the handlers.
+        let nonce = format!("{guild_id}-{}", ctx.id());
+        let shard = &ctx.serenity_context().shard;
+        let mut chunks = {
+            let nonce = nonce.to_owned();
+            collect(shard, move |event| match event {
+                Event::GuildMembersChunk(chunk) if chunk.nonce.as_ref() == Some(&nonce) => Some((
+                    chunk.chunk_count,
+                    chunk
+                        .members
+                        .values()
+                        .map(|m| (m.user.id, m.roles.to_owned()))
+                        .collect::<Vec<_>>(),
+                )),
+                _ => None,
+            })
+        };
+        shard.chunk_guild(guild_id, None, false, ChunkGuildFilter::None, Some(nonce));
+        let mut roles = HashMap::new();
+        let mut received = 0;
+        loop {
+            let (count, members) = tokio::time::timeout(CHUNK_TIMEOUT, chunks.next())
+                .await
+                .ok()
+                .flatten()
+                .whatever_context::<_, BotError>("Timed out waiting for the guild members")?;
+            roles.extend(members);
+            received += 1;
+            if received >= count {
+                break;
+            }
+        }
+        let roles = Arc::new(roles);
+        FETCHED.insert(guild_id, roles.to_owned());
+        Ok(Self { filter, roles })
+    }
+
+    pub fn filter(&self) -> MemberFilter {
+        self.filter
+    }
+
+    pub fn allows(&self, user_id: UserId) -> bool {
+        self.filter
+            .allows(self.roles.get(&user_id).map(Vec::as_slice))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_member_filter() {
+        let (moderator, bot) = (RoleId::new(1), RoleId::new(2));
+        let members = Members {
+            filter: MemberFilter {
+                role: Some(moderator),
+                exclude_role: Some(bot),
+                members_only: false,
+            },
+            roles: Arc::new(HashMap::from([
+                (UserId::new(10), vec![moderator]),
+                (UserId::new(20), vec![moderator, bot]),
+                (UserId::new(30), vec![]),
+            ])),
+        };
+        let allowed = |members: &Members| {
+            [10, 20, 30, 40]
+                .into_iter()
+                .filter(|id| members.allows(UserId::new(*id)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(allowed(&members), [10]);
+
+        // Users who left pass an exclusion unless only members are kept
+        let mut members = Members {
+            filter: MemberFilter {
+                exclude_role: Some(bot),
+                ..Default::default()
+            },
+            ..members
+        };
+        assert_eq!(allowed(&members), [10, 30, 40]);
+        members.filter.members_only = true;
+        assert_eq!(allowed(&members), [10, 30]);
+        assert_eq!(
+            members.filter().describe(),
+            format!("排除 {} · 仅当前成员", bot.mention())
+        );
+    }
+}
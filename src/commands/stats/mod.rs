@@ -1,12 +1,208 @@
+mod backfill;
 mod channel;
+mod chart;
+mod compare;
+mod digest;
+mod engagement;
+mod export;
+mod heatmap;
+mod leaderboard;
+mod members;
+mod my_stats;
+mod time_expr;
+mod tree;
 mod user;
+
+pub use backfill::*;
 pub use channel::*;
-use poise::command;
-use serenity::all::*;
+pub use chart::*;
+pub use digest::*;
+pub use engagement::*;
+pub use export::*;
+pub use heatmap::*;
+pub use my_stats::*;
+pub use time_expr::*;
 pub use user::*;
 
+use std::collections::HashMap;
+
+use chrono::FixedOffset;
+use poise::{ChoiceParameter, command};
+use serenity::all::*;
+use snafu::OptionExt;
+
 use super::Context;
-use crate::error::BotError;
+use crate::{error::BotError, repo::Activity, utils::get_children_channels};
+
+/// How the stats commands treat deleted messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+pub enum DeletedMessages {
+    #[default]
+    #[name = "计入"]
+    Include,
+    #[name = "排除"]
+    Exclude,
+    #[name = "删除率"]
+    Rate,
+}
+
+impl DeletedMessages {
+    /// The count to rank by, deleted messages only when showing deletion rates
+    pub fn count(self, activity: &Activity) -> u64 {
+        match self {
+            DeletedMessages::Include => activity.total,
+            DeletedMessages::Exclude => activity.kept(),
+            DeletedMessages::Rate => activity.deleted,
+        }
+    }
+
+    /// Rank the entries by [`Self::count`], dropping those left with nothing to count
+    pub fn rank<T>(self, data: Vec<(T, Activity)>) -> Vec<(T, u64, Activity)> {
+        let mut data = data
+            .into_iter()
+            .map(|(id, activity)| (id, self.count(&activity), activity))
+            .filter(|(_, count, _)| *count > 0)
+            .collect::<Vec<_>>();
+        data.sort_by(|(_, a, _), (_, b, _)| b.cmp(a));
+        data
+    }
+
+    /// A ranking line, `share` is the percentage of the total count
+    pub fn line(
+        self,
+        rank: usize,
+        name: &str,
+        count: u64,
+        share: f64,
+        activity: &Activity,
+    ) -> String {
+        match self {
+            DeletedMessages::Rate => format!(
+                "{rank}. {}/{} ({:.2}%) - {name}",
+                activity.deleted,
+                activity.total,
+                activity.deletion_rate()
+            ),
+            _ => format!("{rank}. {count} ({share:.2}%) - {name}"),
+        }
+    }
+}
+/// The channel and everything under it, `None` to keep every channel
+async fn channel_filter(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    channel: Option<&GuildChannel>,
+) -> Result<Option<Vec<ChannelId>>, BotError> {
+    let Some(channel) = channel else {
+        return Ok(None);
+    };
+    Ok(Some(
+        get_children_channels(ctx.http(), guild_id, channel)
+            .await?
+            .into_iter()
+            .map(|c| c.id)
+            .collect(),
+    ))
+}
+
+/// Names of channels from the ones seen through the gateway, then from the cache. Deleted
+/// channels keep their last name
+async fn channel_names(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    channel_ids: &[ChannelId],
+) -> Result<HashMap<ChannelId, String>, BotError> {
+    let mut names = ctx
+        .data()
+        .db
+        .known()
+        .channels(channel_ids)
+        .await?
+        .into_iter()
+        .map(|(id, channel)| {
+            let name = match channel.deleted_at {
+                Some(_) => format!("{} (已删除)", channel.name),
+                None => channel.name,
+            };
+            (id, name)
+        })
+        .collect::<HashMap<_, _>>();
+    if let Some(guild) = ctx.cache().guild(guild_id) {
+        let cached = channel_ids
+            .iter()
+            .filter(|id| !names.contains_key(id))
+            .filter_map(|id| {
+                let name = guild
+                    .channels
+                    .get(id)
+                    .map(|c| c.name.to_owned())
+                    .or_else(|| {
+                        guild
+                            .threads
+                            .iter()
+                            .find(|t| t.id == *id)
+                            .map(|t| t.name.to_owned())
+                    })?;
+                Some((*id, name))
+            })
+            .collect::<Vec<_>>();
+        names.extend(cached);
+    }
+    Ok(names)
+}
+
+/// Display names of users from the ones seen through the gateway, then from the cache
+async fn user_names(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    user_ids: &[UserId],
+) -> Result<HashMap<UserId, String>, BotError> {
+    let mut names = ctx
+        .data()
+        .db
+        .known()
+        .users(user_ids)
+        .await?
+        .into_iter()
+        .map(|(id, user)| (id, user.display_name().to_owned()))
+        .collect::<HashMap<_, _>>();
+    let guild = ctx.cache().guild(guild_id);
+    let cached = user_ids
+        .iter()
+        .filter(|id| !names.contains_key(id))
+        .filter_map(|id| {
+            let name = guild
+                .as_ref()
+                .and_then(|g| g.members.get(id).map(|m| m.user.display_name().to_owned()))
+                .or_else(|| ctx.cache().user(*id).map(|u| u.display_name().to_owned()))?;
+            Some((*id, name))
+        })
+        .collect::<Vec<_>>();
+    names.extend(cached);
+    Ok(names)
+}
+
+/// Join ranking lines into an embed description, dropping the lines past its length limit
+fn description_lines(lines: impl IntoIterator<Item = String>) -> String {
+    let mut description = String::new();
+    for line in lines {
+        if description.chars().count() + line.chars().count() + 1 > 4096 {
+            break;
+        }
+        if !description.is_empty() {
+            description.push('\n');
+        }
+        description.push_str(&line);
+    }
+    description
+}
+
+/// The configured `time_offset` as a timezone
+fn local_offset(ctx: Context<'_>) -> Result<FixedOffset, BotError> {
+    FixedOffset::east_opt(ctx.data().cfg.load().time_offset)
+        .whatever_context("Invalid time offset in the configuration")
+}
+
 #[command(slash_command, guild_only, owners_only, ephemeral)]
 /// **危险** 清除所有频道统计数据，请在确认表单中输入 "yes" 以确认。
 pub async fn nuke_channel_stats(ctx: Context<'_>, confirm: String) -> Result<(), BotError> {
@@ -24,19 +220,6 @@ pub async fn nuke_channel_stats(ctx: Context<'_>, confirm: String) -> Result<(),
     Ok(())
 }
 
-pub async fn timestamp_choices<'a>(
-    _ctx: Context<'_>,
-    _partial: &'a str,
-) -> impl Iterator<Item = AutocompleteChoice> + 'a {
-    // 1 day ago and 1 week ago
-    let now = chrono::Utc::now();
-    let one_day_ago = now - chrono::Duration::days(1);
-    let one_week_ago = now - chrono::Duration::weeks(1);
-    [("1 day ago", one_day_ago), ("1 week ago", one_week_ago)]
-        .into_iter()
-        .map(|(name, timestamp)| AutocompleteChoice::new(name.to_string(), timestamp.to_rfc3339()))
-}
-
 pub async fn guild_choices<'a>(
     ctx: Context<'_>,
     _partial: &'a str,
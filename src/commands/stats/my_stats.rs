@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use poise::{CreateReply, command};
+use serenity::all::{colours::roles::DARK_GREEN, *};
+
+use super::{super::Context, local_offset};
+use crate::error::BotError;
+
+const TOP_CHANNELS: usize = 5;
+
+/// Consecutive days with messages up to `today`, a streak is kept alive until the end of the day
+/// after its last message
+fn current_streak(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> u64 {
+    let Some(mut day) = [Some(today), today.pred_opt()]
+        .into_iter()
+        .flatten()
+        .find(|day| days.contains(day))
+    else {
+        return 0;
+    };
+    let mut streak = 0;
+    while days.contains(&day) {
+        streak += 1;
+        let Some(previous) = day.pred_opt() else {
+            break;
+        };
+        day = previous;
+    }
+    streak
+}
+
+#[command(slash_command, guild_only, ephemeral, user_cooldown = 60)]
+/// 查看自己在本服务器的发言统计
+pub async fn my_stats(
+    ctx: Context<'_>,
+    #[description = "是否为临时消息（仅自己可见）, 默认为是"] ephemeral: Option<bool>,
+) -> Result<(), BotError> {
+    let ephemeral = ephemeral.unwrap_or(true);
+    if ephemeral {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
+    let guild_id = ctx.guild_id().expect("guild_only command");
+    let user = ctx.author();
+    let offset = local_offset(ctx)?;
+    let now = Utc::now();
+    let today = now.with_timezone(&offset).date_naive();
+    let today_start = today
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(offset)
+        .single()
+        .expect("fixed offsets are never ambiguous")
+        .to_utc();
+    let windows: [(&str, Option<DateTime<Utc>>); 4] = [
+        ("今日", Some(today_start)),
+        ("近 7 天", Some(now - TimeDelta::days(7))),
+        ("近 30 天", Some(now - TimeDelta::days(30))),
+        ("全部", None),
+    ];
+
+    let db = ctx.data().db.to_owned();
+    let mut embed = CreateEmbed::default()
+        .title(format!("{} 的发言统计", user.display_name()))
+        .thumbnail(user.face())
+        .color(DARK_GREEN);
+    for (name, from) in windows {
+        let users = db
+            .message()
+            .get_user_activity(guild_id, None, from, None::<DateTime<Utc>>)
+            .await?;
+        let value = match users.iter().position(|(id, _)| *id == user.id) {
+            Some(rank) => format!(
+                "{} 条\n第 {} 名 / {} 人",
+                users[rank].1.total,
+                rank + 1,
+                users.len()
+            ),
+            None => "0 条".to_owned(),
+        };
+        embed = embed.field(name, value, true);
+    }
+
+    let channels = db
+        .message()
+        .get_user_channel_activity(
+            guild_id,
+            user.id,
+            None::<DateTime<Utc>>,
+            None::<DateTime<Utc>>,
+        )
+        .await?;
+    if !channels.is_empty() {
+        let top = channels
+            .iter()
+            .take(TOP_CHANNELS)
+            .enumerate()
+            .map(|(i, (channel_id, activity))| {
+                format!(
+                    "{}. {} - {} 条",
+                    i + 1,
+                    channel_id.mention(),
+                    activity.total
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed = embed.field("最常发言的频道", top, false);
+    }
+
+    let days = db
+        .message()
+        .get_hourly_activity(guild_id, None, Some(user.id), None, None)
+        .await?
+        .counts
+        .into_iter()
+        .map(|(hour, _)| hour.with_timezone(&offset).date_naive())
+        .collect::<BTreeSet<_>>();
+    let streak = current_streak(&days, today);
+    embed = embed.field("连续发言", format!("{streak} 天"), true);
+    let first_seen = db.message().first_seen(guild_id, user.id).await?;
+    embed = embed.field(
+        "首次发言",
+        first_seen.map_or_else(
+            || "暂无记录".to_owned(),
+            |t| format!("<t:{}:D>", t.timestamp()),
+        ),
+        true,
+    );
+    if let Some(active_days) =
+        first_seen.map(|t| (today - t.with_timezone(&offset).date_naive()).num_days() + 1)
+    {
+        embed = embed.field(
+            "活跃天数",
+            format!("{} / {active_days} 天", days.len()),
+            true,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(ephemeral))
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Days;
+
+    use super::*;
+
+    #[test]
+    fn test_current_streak() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let ago = |n: u64| today - Days::new(n);
+        let days = BTreeSet::from([ago(0), ago(1), ago(2), ago(4)]);
+        assert_eq!(current_streak(&days, today), 3);
+        // Not posted yet today, yesterday still counts
+        let days = BTreeSet::from([ago(1), ago(2)]);
+        assert_eq!(current_streak(&days, today), 2);
+        let days = BTreeSet::from([ago(2), ago(3)]);
+        assert_eq!(current_streak(&days, today), 0);
+        assert_eq!(current_streak(&BTreeSet::new(), today), 0);
+    }
+}
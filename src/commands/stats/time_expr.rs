@@ -0,0 +1,376 @@
+use std::str::FromStr;
+
+use chrono::{
+    DateTime, Datelike, Days, FixedOffset, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    Utc,
+};
+use serenity::all::AutocompleteChoice;
+use snafu::Snafu;
+
+use super::{super::Context, local_offset};
+use crate::error::BotError;
+
+/// Expressions offered by the autocomplete, completed from what was typed so far
+const SUGGESTIONS: [&str; 10] = [
+    "today",
+    "yesterday",
+    "this week",
+    "last week",
+    "this month",
+    "last month",
+    "this year",
+    "24h",
+    "7d",
+    "30d",
+];
+/// Units of the relative spans, like `7d`
+const UNITS: [char; 4] = ['m', 'h', 'd', 'w'];
+/// Autocomplete choices Discord shows at most
+const MAX_CHOICES: usize = 25;
+
+#[derive(Debug, Snafu)]
+#[snafu(display(
+    "无法识别的时间 `{input}`, 可以使用 today、yesterday、this week、last month、7d、2025-07-01、2025-07-01..2025-07-15 或 RFC3339 时间"
+))]
+pub struct TimeExprError {
+    input: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// One side of a time expression, a span of time that is resolved when the command runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    Now,
+    /// An exact time, with its own offset
+    Instant(DateTime<FixedOffset>),
+    /// A local time in the configured offset
+    Local(NaiveDateTime),
+    /// A local date
+    Date(NaiveDate),
+    /// The local period `back` periods before the current one, weeks start on Monday
+    Calendar {
+        period: Period,
+        back: u32,
+    },
+    /// The last `span` up to now
+    Last(TimeDelta),
+}
+
+impl Anchor {
+    /// Whether the anchor is a single point in time rather than a span
+    fn is_point(self) -> bool {
+        matches!(self, Anchor::Now | Anchor::Instant(_) | Anchor::Local(_))
+    }
+
+    fn parse(input: &str) -> Option<Self> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+            return Some(Anchor::Instant(time));
+        }
+        let calendar = |period, back| Some(Anchor::Calendar { period, back });
+        match input.to_lowercase().as_str() {
+            "now" | "现在" => Some(Anchor::Now),
+            "today" | "今天" => calendar(Period::Day, 0),
+            "yesterday" | "昨天" => calendar(Period::Day, 1),
+            "this week" | "本周" => calendar(Period::Week, 0),
+            "last week" | "上周" => calendar(Period::Week, 1),
+            "this month" | "本月" => calendar(Period::Month, 0),
+            "last month" | "上月" => calendar(Period::Month, 1),
+            "this year" | "今年" => calendar(Period::Year, 0),
+            "last year" | "去年" => calendar(Period::Year, 1),
+            input => {
+                if let Ok(time) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+                    return Some(Anchor::Local(time));
+                }
+                if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+                    return Some(Anchor::Date(date));
+                }
+                let unit = input.chars().last().filter(|unit| UNITS.contains(unit))?;
+                let count = input[..input.len() - 1].parse::<u32>().ok()?.into();
+                let span = match unit {
+                    'm' => TimeDelta::try_minutes(count),
+                    'h' => TimeDelta::try_hours(count),
+                    'd' => TimeDelta::try_days(count),
+                    _ => TimeDelta::try_weeks(count),
+                }?;
+                (count > 0).then_some(Anchor::Last(span))
+            }
+        }
+    }
+
+    /// Start and end of the span
+    fn resolve(self, now: DateTime<Utc>, offset: FixedOffset) -> (DateTime<Utc>, DateTime<Utc>) {
+        let local_start = |day: NaiveDate| local_time(day.and_time(NaiveTime::MIN), offset);
+        match self {
+            Anchor::Now => (now, now),
+            Anchor::Instant(time) => (time.to_utc(), time.to_utc()),
+            Anchor::Local(time) => (local_time(time, offset), local_time(time, offset)),
+            Anchor::Date(day) => (local_start(day), local_start(day + Days::new(1))),
+            Anchor::Last(span) => (now - span, now),
+            Anchor::Calendar { period, back } => {
+                let today = now.with_timezone(&offset).date_naive();
+                let (start, end) = match period {
+                    Period::Day => {
+                        let start = today - Days::new(back.into());
+                        (start, start + Days::new(1))
+                    }
+                    Period::Week => {
+                        let monday =
+                            today - Days::new(today.weekday().num_days_from_monday().into());
+                        let start = monday - Days::new(u64::from(back) * 7);
+                        (start, start + Days::new(7))
+                    }
+                    Period::Month => {
+                        let first = today.with_day(1).expect("every month has a first day");
+                        let start = first - Months::new(back);
+                        (start, start + Months::new(1))
+                    }
+                    Period::Year => {
+                        let first = today.with_ordinal(1).expect("every year has a first day");
+                        let start = first - Months::new(back * 12);
+                        (start, start + Months::new(12))
+                    }
+                };
+                (local_start(start), local_start(end))
+            }
+        }
+    }
+}
+
+fn local_time(time: NaiveDateTime, offset: FixedOffset) -> DateTime<Utc> {
+    time.and_local_timezone(offset)
+        .single()
+        .expect("fixed offsets are never ambiguous")
+        .to_utc()
+}
+
+/// Start and end of a time range, `None` where it is left open
+pub type TimeRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// A time or time range typed into a `from` or `to` option, like `yesterday`, `7d` or
+/// `2025-07-01..2025-07-15`, interpreted in the configured `time_offset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeExpr {
+    start: Option<Anchor>,
+    /// The same as `start` unless written as `start..end`, either side may be left open
+    end: Option<Anchor>,
+}
+
+impl FromStr for TimeExpr {
+    type Err = TimeExprError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || TimeExprError {
+            input: input.to_owned(),
+        };
+        let side = |side: &str| match side.trim() {
+            "" => Ok(None),
+            side => Anchor::parse(side).map(Some).ok_or_else(error),
+        };
+        let expr = match input.split_once("..") {
+            Some((start, end)) => TimeExpr {
+                start: side(start)?,
+                end: side(end)?,
+            },
+            None => {
+                let anchor = side(input)?;
+                TimeExpr {
+                    start: anchor,
+                    end: anchor,
+                }
+            }
+        };
+        if expr.start.is_none() && expr.end.is_none() {
+            return Err(error());
+        }
+        Ok(expr)
+    }
+}
+
+impl TimeExpr {
+    /// Start of the first span and end of the last one, `None` where the range is left open
+    fn resolve(self, now: DateTime<Utc>, offset: FixedOffset) -> TimeRange {
+        (
+            self.start.map(|start| start.resolve(now, offset).0),
+            self.end.map(|end| end.resolve(now, offset).1),
+        )
+    }
+}
+
+/// The range between the start of `from` and the end of `to`. Without `to`, `from` covers the
+/// span it names and a single point in time is left open
+fn resolve_range(
+    from: Option<TimeExpr>,
+    to: Option<TimeExpr>,
+    now: DateTime<Utc>,
+    offset: FixedOffset,
+) -> TimeRange {
+    let start = from.and_then(|from| from.resolve(now, offset).0);
+    let end = match to {
+        Some(to) => to.resolve(now, offset).1,
+        None => from
+            .and_then(|from| from.end)
+            .filter(|end| !end.is_point())
+            .map(|end| end.resolve(now, offset).1),
+    };
+    (start, end)
+}
+
+/// Resolve the `from` and `to` options of a command in the configured `time_offset`
+pub fn time_range(
+    ctx: Context<'_>,
+    from: Option<TimeExpr>,
+    to: Option<TimeExpr>,
+) -> Result<TimeRange, BotError> {
+    Ok(resolve_range(from, to, Utc::now(), local_offset(ctx)?))
+}
+
+/// Name and value of the autocomplete choice of an expression, named after the range it
+/// resolves to right now
+fn preview(expr: &str, now: DateTime<Utc>, offset: FixedOffset) -> Option<(String, String)> {
+    let (start, end) = expr.parse::<TimeExpr>().ok()?.resolve(now, offset);
+    let format = |time: Option<DateTime<Utc>>| {
+        time.map_or_else(
+            || "不限".to_owned(),
+            |t| {
+                t.with_timezone(&offset)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            },
+        )
+    };
+    let range = if start == end {
+        format(start)
+    } else {
+        format!("{} - {}", format(start), format(end))
+    };
+    Some((format!("{expr} → {range}"), expr.to_owned()))
+}
+
+/// What was typed with the resolved range, then the suggestions it could be completed to
+fn choices(partial: &str, now: DateTime<Utc>, offset: FixedOffset) -> Vec<(String, String)> {
+    let partial = partial.trim();
+    // Complete the end of a range, or the number of a relative span
+    let (head, tail) = match partial.rsplit_once("..") {
+        Some((head, tail)) => (format!("{head}.."), tail.trim_start()),
+        None => (String::new(), partial),
+    };
+    let typed = tail.to_lowercase();
+    let mut completions = SUGGESTIONS
+        .iter()
+        .filter(|s| s.starts_with(&typed) && **s != typed)
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    if !tail.is_empty() && tail.chars().all(|c| c.is_ascii_digit()) {
+        completions.extend(UNITS.iter().map(|unit| format!("{tail}{unit}")));
+    }
+    std::iter::once(partial.to_owned())
+        .filter(|p| !p.is_empty())
+        .chain(completions.into_iter().map(|c| format!("{head}{c}")))
+        .filter_map(|expr| preview(&expr, now, offset))
+        .take(MAX_CHOICES)
+        .collect()
+}
+
+pub async fn timestamp_choices<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = AutocompleteChoice> + 'a {
+    let offset = local_offset(ctx).unwrap_or(FixedOffset::east_opt(0).expect("UTC is valid"));
+    choices(partial, Utc::now(), offset)
+        .into_iter()
+        .map(|(name, value)| AutocompleteChoice::new(name, value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_time_expr() {
+        let offset = FixedOffset::east_opt(8 * 3600).unwrap();
+        let at = |month: u32, day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(2026, month, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        // Sunday 2026-10-18 20:00 UTC is Monday 04:00 in UTC+8
+        let now = at(10, 18, 20);
+        let range = |from: &str, to: Option<&str>| {
+            resolve_range(
+                Some(from.parse().unwrap()),
+                to.map(|to| to.parse().unwrap()),
+                now,
+                offset,
+            )
+        };
+        assert_eq!(
+            range("today", None),
+            (Some(at(10, 18, 16)), Some(at(10, 19, 16)))
+        );
+        assert_eq!(
+            range("yesterday", Some("yesterday")),
+            (Some(at(10, 17, 16)), Some(at(10, 18, 16)))
+        );
+        assert_eq!(
+            range("This Week", None),
+            (Some(at(10, 18, 16)), Some(at(10, 25, 16)))
+        );
+        assert_eq!(range("last month..", None), (Some(at(8, 31, 16)), None));
+        assert_eq!(
+            range("上月..上月", None),
+            (Some(at(8, 31, 16)), Some(at(9, 30, 16)))
+        );
+        assert_eq!(range("7d", None), (Some(at(10, 11, 20)), Some(now)));
+        assert_eq!(
+            range("2026-10-01T00:00:00Z", None),
+            (Some(at(10, 1, 0)), None)
+        );
+        assert_eq!(
+            range("2026-07-01..2026-07-15", None),
+            (Some(at(6, 30, 16)), Some(at(7, 15, 16)))
+        );
+        assert_eq!(
+            range("2026-10-01T00:00:00Z", Some("2026-10-02 08:00")),
+            (Some(at(10, 1, 0)), Some(at(10, 2, 0)))
+        );
+        assert_eq!(range("..yesterday", None), (None, Some(at(10, 18, 16))));
+        for invalid in ["", "..", "0d", "7x", "last fortnight", "2026-13-01"] {
+            assert!(invalid.parse::<TimeExpr>().is_err(), "{invalid}");
+        }
+
+        let names = |partial| {
+            choices(partial, now, offset)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names("last"),
+            [
+                "last week → 2026-10-12 00:00 - 2026-10-19 00:00",
+                "last month → 2026-09-01 00:00 - 2026-10-01 00:00",
+            ]
+        );
+        assert_eq!(
+            names("3")[..2],
+            [
+                "30d → 2026-09-19 04:00 - 2026-10-19 04:00",
+                "3m → 2026-10-19 03:57 - 2026-10-19 04:00"
+            ]
+        );
+        assert_eq!(
+            names("2026-10-01..yes"),
+            ["2026-10-01..yesterday → 2026-10-01 00:00 - 2026-10-19 00:00"]
+        );
+        assert_eq!(names("").len(), SUGGESTIONS.len());
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+    time::Duration,
+};
+
+use moka::sync::Cache;
+use poise::ChoiceParameter;
+use serenity::all::*;
+use tracing::warn;
+
+use super::super::Context;
+use crate::{
+    error::BotError,
+    repo::Activity,
+    utils::{Archive, get_archived_threads},
+};
+
+/// What channel activity is summed into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+pub enum ChannelGrouping {
+    #[default]
+    #[name = "不合并"]
+    None,
+    #[name = "子区与帖子合并到所属频道"]
+    Channel,
+    #[name = "合并到分类"]
+    Category,
+}
+
+/// Channels looked up one by one per load at most, after the archives were searched
+const MAX_LOOKUPS: usize = 25;
+/// How long a channel that could not be found is left alone before being looked up again
+const UNRESOLVED_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Channels neither the archives nor a direct lookup could find, most likely deleted
+static UNRESOLVED: LazyLock<Cache<ChannelId, ()>> = LazyLock::new(|| {
+    Cache::builder()
+        .max_capacity(10_000)
+        .time_to_live(UNRESOLVED_LIFETIME)
+        .build()
+});
+
+fn is_thread(kind: ChannelType) -> bool {
+    matches!(
+        kind,
+        ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread
+    )
+}
+
+/// Where the channels, threads and forum posts of a guild sit
+#[derive(Debug)]
+pub(super) struct ChannelTree {
+    /// Parent and type of every channel
+    channels: HashMap<ChannelId, (Option<ChannelId>, ChannelType)>,
+    /// Current channels that may hold archived threads or forum posts
+    thread_parents: Vec<ChannelId>,
+}
+
+impl ChannelTree {
+    /// Build the tree from the channels seen before, then the cached channels and active
+    /// threads, so archived threads and deleted channels keep their last place
+    pub async fn load(ctx: Context<'_>, guild_id: GuildId) -> Result<Self, BotError> {
+        let known = ctx.data().db.known().guild_channels(guild_id).await?;
+        let cached = ctx
+            .cache()
+            .guild(guild_id)
+            .map(|guild| {
+                guild
+                    .channels
+                    .values()
+                    .chain(&guild.threads)
+                    .map(|c| (c.id, c.parent_id, c.kind))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mut tree = Self::new(
+            known
+                .iter()
+                .map(|c| (c.channel_id(), c.parent_id(), c.kind()))
+                .chain(cached.iter().copied()),
+        );
+        tree.thread_parents = cached
+            .into_iter()
+            .filter(|(_, _, kind)| {
+                matches!(
+                    kind,
+                    ChannelType::Text | ChannelType::News | ChannelType::Forum
+                )
+            })
+            .map(|(id, _, _)| id)
+            .collect();
+        Ok(tree)
+    }
+
+    /// Look up the channels among `ids` the tree does not know, archived threads and forum posts
+    /// that were never seen through the gateway, and remember them for the next time
+    pub async fn resolve(
+        &mut self,
+        ctx: Context<'_>,
+        ids: impl IntoIterator<Item = ChannelId>,
+    ) -> Result<(), BotError> {
+        let mut unknown = self.unknown(ids);
+        unknown.retain(|id| !UNRESOLVED.contains_key(id));
+        let mut found = Vec::new();
+        // Every archived thread listed on the way is kept, not just the ones asked for
+        'parents: for parent_id in &self.thread_parents {
+            for archive in [Archive::Public, Archive::Private] {
+                if unknown.is_empty() {
+                    break 'parents;
+                }
+                let listed = get_archived_threads(ctx.http(), *parent_id, archive, |page| {
+                    for thread in page {
+                        unknown.remove(&thread.id);
+                    }
+                    !unknown.is_empty()
+                })
+                .await;
+                match listed {
+                    Ok(threads) => found.extend(threads),
+                    Err(e) => warn!("Failed to list archived threads of {parent_id}: {e}"),
+                }
+            }
+        }
+        // Under a channel that is gone or out of sight
+        for id in unknown.into_iter().take(MAX_LOOKUPS) {
+            match id.to_channel(ctx).await {
+                Ok(Channel::Guild(channel)) => found.push(channel),
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Failed to look up channel {id}: {e}");
+                    UNRESOLVED.insert(id, ());
+                }
+            }
+        }
+        if found.is_empty() {
+            return Ok(());
+        }
+        ctx.data().db.known().save_channels(&found).await?;
+        self.channels
+            .extend(found.iter().map(|c| (c.id, (c.parent_id, c.kind))));
+        Ok(())
+    }
+
+    /// Later entries of the same channel replace the earlier ones
+    fn new(
+        channels: impl IntoIterator<Item = (ChannelId, Option<ChannelId>, ChannelType)>,
+    ) -> Self {
+        Self {
+            channels: channels
+                .into_iter()
+                .map(|(id, parent_id, kind)| (id, (parent_id, kind)))
+                .collect(),
+            thread_parents: Vec::new(),
+        }
+    }
+
+    fn unknown(&self, ids: impl IntoIterator<Item = ChannelId>) -> HashSet<ChannelId> {
+        ids.into_iter()
+            .filter(|id| !self.channels.contains_key(id))
+            .collect()
+    }
+
+    pub fn is_category(&self, id: ChannelId) -> bool {
+        self.channels
+            .get(&id)
+            .is_some_and(|(_, kind)| *kind == ChannelType::Category)
+    }
+
+    /// The channel a thread or forum post belongs to, anything else is its own channel
+    pub fn channel_of(&self, id: ChannelId) -> ChannelId {
+        match self.channels.get(&id) {
+            Some((Some(parent_id), kind)) if is_thread(*kind) => *parent_id,
+            _ => id,
+        }
+    }
+
+    /// The category holding a channel, thread or forum post, the channel itself when it has none
+    pub fn category_of(&self, id: ChannelId) -> ChannelId {
+        let channel_id = self.channel_of(id);
+        match self.channels.get(&channel_id) {
+            Some((Some(parent_id), _)) => *parent_id,
+            _ => channel_id,
+        }
+    }
+
+    /// Sum the activity of every channel into what `grouping` puts it under
+    pub fn roll_up(
+        &self,
+        entries: &[(ChannelId, Activity)],
+        grouping: ChannelGrouping,
+    ) -> Vec<(ChannelId, Activity)> {
+        let mut merged = HashMap::<ChannelId, Activity>::new();
+        for (id, activity) in entries {
+            let id = match grouping {
+                ChannelGrouping::None => *id,
+                ChannelGrouping::Channel => self.channel_of(*id),
+                ChannelGrouping::Category => self.category_of(*id),
+            };
+            let sum = merged.entry(id).or_default();
+            sum.total += activity.total;
+            sum.deleted += activity.deleted;
+        }
+        merged.into_iter().collect()
+    }
+
+    /// The activity under each category and channel: channels by their category, threads and
+    /// forum posts by their channel
+    pub fn children(
+        &self,
+        entries: &[(ChannelId, Activity)],
+    ) -> HashMap<ChannelId, Vec<(ChannelId, Activity)>> {
+        let mut children = HashMap::<_, Vec<_>>::new();
+        let threads = entries
+            .iter()
+            .filter(|(id, _)| self.channel_of(*id) != *id)
+            .map(|(id, activity)| (self.channel_of(*id), (*id, *activity)));
+        let channels = self
+            .roll_up(entries, ChannelGrouping::Channel)
+            .into_iter()
+            .filter(|(id, _)| self.category_of(*id) != *id)
+            .map(|(id, activity)| (self.category_of(id), (id, activity)));
+        for (parent_id, child) in threads.chain(channels) {
+            children.entry(parent_id).or_default().push(child);
+        }
+        children
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_channel_tree() {
+        let id = ChannelId::new;
+        let (category, forum, text, post, thread, lone) =
+            (id(1), id(10), id(11), id(100), id(101), id(20));
+        let tree = ChannelTree::new([
+            (category, None, ChannelType::Category),
+            (forum, Some(category), ChannelType::Forum),
+            (text, None, ChannelType::Text),
+            (post, Some(forum), ChannelType::PublicThread),
+            (thread, Some(text), ChannelType::PublicThread),
+            (lone, None, ChannelType::Text),
+            // Moved under the category since it was last seen
+            (text, Some(category), ChannelType::Text),
+        ]);
+        let activity = |total: u64| Activity { total, deleted: 0 };
+        let entries = [
+            (forum, activity(1)),
+            (post, activity(2)),
+            (text, activity(4)),
+            (thread, activity(8)),
+            (lone, activity(16)),
+            (id(999), activity(32)),
+        ];
+        let sorted = |mut entries: Vec<(ChannelId, Activity)>| {
+            entries.sort_unstable_by_key(|(id, _)| *id);
+            entries
+                .into_iter()
+                .map(|(id, activity)| (id.get(), activity.total))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sorted(tree.roll_up(&entries, ChannelGrouping::Channel)),
+            [(10, 3), (11, 12), (20, 16), (999, 32)]
+        );
+        assert_eq!(
+            sorted(tree.roll_up(&entries, ChannelGrouping::Category)),
+            [(1, 15), (20, 16), (999, 32)]
+        );
+        let mut children = tree.children(&entries);
+        assert_eq!(children.len(), 3);
+        assert_eq!(
+            sorted(children.remove(&category).unwrap()),
+            [(10, 3), (11, 12)]
+        );
+        assert_eq!(sorted(children.remove(&forum).unwrap()), [(100, 2)]);
+        assert_eq!(sorted(children.remove(&text).unwrap()), [(101, 8)]);
+        assert!(tree.is_category(category) && !tree.is_category(forum));
+        assert_eq!(
+            tree.unknown(entries.iter().map(|(id, _)| *id)),
+            HashSet::from([id(999)])
+        );
+    }
+}
@@ -1,122 +1,216 @@
-use std::time::Instant;
-
-use chrono::{DateTime, Utc};
-use futures::{StreamExt, stream};
+use chrono::{DateTime, TimeDelta, Utc};
 use poise::{CreateReply, command};
 use serenity::all::{colours::roles::DARK_GREEN, *};
+use snafu::ResultExt;
 
 use super::{
     super::{Context, check_admin},
-    guild_choices, timestamp_choices,
+    DeletedMessages, TimeExpr, channel_filter,
+    chart::{Bucket, ChartStyle, MAX_BUCKETS, bucket_counts, render_chart, spread_footer},
+    guild_choices,
+    leaderboard::{LeaderboardQuery, View, leaderboard},
+    local_offset,
+    members::MemberFilter,
+    time_range, timestamp_choices,
+    tree::ChannelGrouping,
 };
-use crate::{error::BotError, utils::get_children_channels};
+use crate::error::BotError;
 
+#[allow(clippy::too_many_arguments)]
 #[command(slash_command, guild_only, ephemeral, check = "check_admin")]
 /// 获取用户活跃度统计
 pub async fn user_stats(
     ctx: Context<'_>,
-    #[description = "显示前 N 个活跃用户，默认为 20"]
+    #[description = "每页显示的用户数，默认为 20"]
     #[min = 1]
     #[max = 50]
-    top_n: Option<usize>,
+    page_size: Option<usize>,
     #[description = "指定服务器 ID, 默认为当前所在服务器"]
     #[autocomplete = "guild_choices"]
     guild: Option<Guild>,
     #[description = "指定频道, 默认为所有频道"] channel: Option<GuildChannel>,
-    #[description = "统计时间范围开始时间, 格式为 RFC3339, 默认无限制"]
+    #[description = "统计时间范围开始时间, 如 today、7d、2025-07-01..2025-07-15, 默认无限制"]
     #[autocomplete = "timestamp_choices"]
-    from: Option<DateTime<Utc>>,
-    #[description = "统计时间范围结束时间, 格式为 RFC3339, 默认为现在"]
+    from: Option<TimeExpr>,
+    #[description = "统计时间范围结束时间, 如 yesterday、2025-07-15, 默认为现在"]
     #[autocomplete = "timestamp_choices"]
-    to: Option<DateTime<Utc>>,
+    to: Option<TimeExpr>,
+    #[description = "已删除的消息: 计入, 排除或统计删除率, 默认计入"] deleted: Option<
+        DeletedMessages,
+    >,
+    #[description = "与上一个等长的时间段对比, 需要指定开始时间"] compare: Option<bool>,
+    #[description = "只统计拥有该身份组的成员"] role: Option<Role>,
+    #[description = "不统计拥有该身份组的成员"] exclude_role: Option<Role>,
+    #[description = "只统计仍在服务器中的成员, 默认为否"] members_only: Option<bool>,
     #[description = "是否为临时消息（仅自己可见）"] ephemeral: Option<bool>,
 ) -> Result<(), BotError> {
+    let (from, to) = time_range(ctx, from, to)?;
+    let compare = compare.unwrap_or(false);
+    if compare && from.is_none() {
+        ctx.say("❌ 对比模式需要指定开始时间。").await?;
+        return Ok(());
+    }
     let ephemeral = ephemeral.unwrap_or(true);
-    let top_n = top_n.unwrap_or(20); // 默认显示前20个用户
+    let page_size = page_size.unwrap_or(20); // 默认每页显示20个用户
     if ephemeral {
         ctx.defer_ephemeral().await?;
     } else {
         ctx.defer().await?;
     }
     let guild = guild.unwrap_or_else(|| ctx.guild().unwrap().to_owned());
-    let guild_id = guild.id;
-    let guild_name = guild.name.to_owned();
-    let now = Instant::now();
-    let channels = if let Some(ref channel) = channel {
-        Some(
-            get_children_channels(ctx.http(), guild.id, channel)
-                .await?
-                .into_iter()
-                .map(|c| c.id)
-                .collect::<Vec<_>>(),
-        )
-    } else {
-        None
+    if [&role, &exclude_role]
+        .into_iter()
+        .flatten()
+        .any(|r| r.guild_id != guild.id)
+    {
+        ctx.say("❌ 身份组必须属于所统计的服务器。").await?;
+        return Ok(());
+    }
+    let channel_ids = channel_filter(ctx, guild.id, channel.as_ref()).await?;
+    let query = LeaderboardQuery {
+        guild_id: guild.id,
+        guild_name: guild.name,
+        channel,
+        from,
+        to,
+        deleted: deleted.unwrap_or_default(),
+        compare,
+        members: MemberFilter {
+            role: role.map(|r| r.id),
+            exclude_role: exclude_role.map(|r| r.id),
+            members_only: members_only.unwrap_or(false),
+        },
+        grouping: ChannelGrouping::None,
     };
+    leaderboard(ctx, query, channel_ids, View::Users, page_size, ephemeral).await
+}
+
+const DETAIL_CHANNELS: usize = 15;
+const DETAIL_CHART: &str = "daily.png";
+
+#[command(slash_command, guild_only, ephemeral, check = "check_admin")]
+/// 查看单个用户的详细活跃度统计
+pub async fn user_detail(
+    ctx: Context<'_>,
+    #[description = "要查看的用户"] user: User,
+    #[description = "指定服务器 ID, 默认为当前所在服务器"]
+    #[autocomplete = "guild_choices"]
+    guild: Option<Guild>,
+    #[description = "统计时间范围开始时间, 如 today、7d、2025-07-01..2025-07-15, 默认无限制"]
+    #[autocomplete = "timestamp_choices"]
+    from: Option<TimeExpr>,
+    #[description = "统计时间范围结束时间, 如 yesterday、2025-07-15, 默认为现在"]
+    #[autocomplete = "timestamp_choices"]
+    to: Option<TimeExpr>,
+    #[description = "是否为临时消息（仅自己可见）"] ephemeral: Option<bool>,
+) -> Result<(), BotError> {
+    let (from, to) = time_range(ctx, from, to)?;
+    let ephemeral = ephemeral.unwrap_or(true);
+    if ephemeral {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
+    let guild_id = guild
+        .map(|g| g.id)
+        .or_else(|| ctx.guild_id())
+        .expect("Guild ID should be present in a guild context");
+    let guild_name = guild_id.name(ctx).unwrap_or_else(|| guild_id.to_string());
     let db = ctx.data().db.to_owned();
-    let data = db
+    let channels = db
         .message()
-        .get_user_stats(guild_id, channels.as_deref(), from, to)
+        .get_user_channel_activity(guild_id, user.id, from, to)
         .await?;
-    let db_duration = now.elapsed();
+    let total = channels.iter().map(|(_, a)| a.total).sum::<u64>();
+    if total == 0 {
+        ctx.say(format!("{} 在该时间范围内没有发言记录。", user.mention()))
+            .await?;
+        return Ok(());
+    }
+    let guild_total = db
+        .message()
+        .get_channel_activity(guild_id, from, to)
+        .await?
+        .iter()
+        .map(|(_, a)| a.total)
+        .sum::<u64>();
+    let first_seen = db.message().first_seen(guild_id, user.id).await?;
+    let last_seen = db.message().last_seen(guild_id, user.id).await?;
 
-    if data.is_empty() {
-        ctx.send(
-            CreateReply::default()
-                .content("该服务器今天还没有发言记录。")
-                .ephemeral(true),
+    // Daily activity from the first message in range, by week when that is too many days
+    let chart_to = to.unwrap_or_else(Utc::now);
+    let chart_from = from
+        .or(first_seen)
+        .unwrap_or(chart_to - TimeDelta::days(30));
+    let bucket = if (chart_to - chart_from).num_days() > MAX_BUCKETS {
+        Bucket::Week
+    } else {
+        Bucket::Day
+    };
+    let hourly = db
+        .message()
+        .get_hourly_activity(
+            guild_id,
+            None,
+            Some(user.id),
+            Some(chart_from),
+            Some(chart_to),
         )
         .await?;
-        return Ok(());
-    }
-    let sum = data.iter().map(|(_, count)| *count).sum::<u64>();
-    let now = Instant::now();
-    let ranking_text = data
-        .into_iter()
-        .take(top_n)
-        .map(async |(user_id, count)| {
-            let name = user_id
-                .to_user(ctx)
-                .await
-                .map(|u| u.mention().to_string())
-                .unwrap_or_else(|_| user_id.to_string());
-            (name, count)
-        })
-        .collect::<stream::FuturesOrdered<_>>()
-        .collect::<Vec<_>>()
+    let footer = spread_footer(&hourly, chart_from);
+    let buckets = bucket_counts(
+        &hourly.counts,
+        bucket,
+        local_offset(ctx)?,
+        chart_from,
+        chart_to,
+    );
+    let active_days = buckets.iter().filter(|(_, count)| *count > 0).count();
+    let png = tokio::task::spawn_blocking(move || render_chart(&buckets, bucket, ChartStyle::Bar))
         .await
-        .into_iter()
+        .whatever_context::<_, BotError>("Chart rendering panicked")??;
+
+    let breakdown = channels
+        .iter()
+        .take(DETAIL_CHANNELS)
         .enumerate()
-        .map(|(i, (name, count))| {
+        .map(|(i, (channel_id, activity))| {
             format!(
                 "{}. {} ({:.2}%) - {}",
                 i + 1,
-                count,
-                (count * 100) as f64 / sum as f64,
-                name,
+                activity.total,
+                (activity.total * 100) as f64 / total as f64,
+                channel_id.mention()
             )
         })
         .collect::<Vec<_>>()
         .join("\n");
-    let network_duration = now.elapsed();
-    let embed = CreateEmbed::default()
-        .title(format!("{guild_name} 用户活跃度统计"))
-        .field("总条数", sum.to_string(), false)
-        .field(
-            "频道",
-            channel
-                .map(|c| c.mention().to_string())
-                .unwrap_or_else(|| "所有频道".into()),
-            false,
+    let seen = |time: Option<DateTime<Utc>>| {
+        time.map_or_else(
+            || "暂无记录".to_owned(),
+            |t| format!("<t:{}:f>", t.timestamp()),
         )
+    };
+    let mut embed = CreateEmbed::default()
+        .title(format!("{} 在 {guild_name} 的活跃度", user.display_name()))
+        .thumbnail(user.face())
+        .description(breakdown)
+        .field("总条数", total.to_string(), true)
         .field(
-            "数据库查询耗时",
-            format!("{}ms", db_duration.as_millis()),
+            "占服务器发言",
+            format!("{:.2}%", (total * 100) as f64 / guild_total.max(1) as f64),
             true,
         )
+        .field("发言频道数", channels.len().to_string(), true)
+        .field("首次发言", seen(first_seen), true)
+        .field("最近发言", seen(last_seen), true)
         .field(
-            "网络请求耗时",
-            format!("{}ms", network_duration.as_millis()),
+            if bucket == Bucket::Week {
+                "活跃周数"
+            } else {
+                "活跃天数"
+            },
+            active_days.to_string(),
             true,
         )
         .field(
@@ -128,10 +222,15 @@ pub async fn user_stats(
             ),
             false,
         )
-        .description(ranking_text)
+        .image(format!("attachment://{DETAIL_CHART}"))
         .color(DARK_GREEN);
-    let reply = CreateReply::default().embed(embed).ephemeral(ephemeral);
+    if let Some(footer) = footer {
+        embed = embed.footer(footer);
+    }
+    let reply = CreateReply::default()
+        .embed(embed)
+        .attachment(CreateAttachment::bytes(png, DETAIL_CHART))
+        .ephemeral(ephemeral);
     ctx.send(reply).await?;
-
     Ok(())
 }
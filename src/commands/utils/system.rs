@@ -7,7 +7,7 @@ use serenity::all::{
 use sysinfo::System;
 
 use super::super::Context;
-use crate::error::BotError;
+use crate::{error::BotError, handlers::prune_messages};
 
 #[command(
     slash_command,
@@ -148,3 +148,22 @@ pub async fn vacuum(ctx: Context<'_>) -> Result<(), BotError> {
         .await?;
     Ok(())
 }
+
+#[command(prefix_command, owners_only, ephemeral)]
+pub async fn prune(ctx: Context<'_>) -> Result<(), BotError> {
+    let cfg = ctx.data().cfg.load_full();
+    let Some(retention) = cfg.message_retention_days else {
+        ctx.say("未配置消息保留期限 (messageRetentionDays)，不会清理任何消息。")
+            .await?;
+        return Ok(());
+    };
+    let msg = ctx.say("正在清理过期消息，请稍候...").await?;
+    let pruned = prune_messages(&ctx.data().db, &cfg).await?;
+    msg.edit(
+        ctx,
+        CreateReply::default()
+            .content(format!("已清理 {pruned} 条超过 {retention} 天的消息记录。")),
+    )
+    .await?;
+    Ok(())
+}
@@ -10,6 +10,7 @@ use figment::{
     Figment,
     providers::{Env, Format, Json},
 };
+use poise::ChoiceParameter;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_with::{DurationSeconds, serde_as};
@@ -18,8 +19,12 @@ use serenity::{
     prelude::TypeMapKey,
 };
 use snafu::{OptionExt, ResultExt};
+use tracing::{info, warn};
 
-use crate::error::BotError;
+use crate::{
+    error::BotError,
+    utils::{SecretRule, default_secret_rules},
+};
 
 #[serde_as]
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
@@ -37,10 +42,41 @@ pub struct BotCfg {
     pub tree_holes: HashMap<ChannelId, Duration>,
     pub toilets: HashSet<ChannelId>,
     pub extra_owners: HashSet<UserId>,
+    #[serde(default = "default_secret_rules")]
+    pub secret_rules: Vec<SecretRule>,
+    /// Days to keep raw message rows for, older rows are pruned once rolled up. Kept forever if unset
+    #[serde(default)]
+    pub message_retention_days: Option<u32>,
+    /// Run an incremental vacuum after pruning, only effective with `auto_vacuum = INCREMENTAL`
+    #[serde(default)]
+    pub incremental_vacuum: bool,
+    /// Activity digests posted on a schedule, per guild
+    #[serde(default)]
+    pub digests: HashMap<GuildId, DigestCfg>,
     #[serde(skip)]
     pub path: PathBuf,
 }
 
+/// How often an activity digest is posted, periods follow the configured `time_offset`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+#[serde(rename_all = "camelCase")]
+pub enum DigestPeriod {
+    /// Every local day, after midnight
+    #[name = "每日"]
+    Daily,
+    /// Every week, after midnight on Monday
+    #[default]
+    #[name = "每周"]
+    Weekly,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestCfg {
+    pub channel_id: ChannelId,
+    pub period: DigestPeriod,
+}
+
 impl TypeMapKey for BotCfg {
     type Value = Arc<ArcSwap<BotCfg>>;
 }
@@ -72,6 +108,31 @@ impl BotCfg {
         })
     }
 
+    /// Poll the configuration file and swap in the new configuration whenever it changes.
+    ///
+    /// A configuration that fails to parse is logged and ignored, the old one stays in effect.
+    pub async fn watch(cfg: Arc<ArcSwap<BotCfg>>) {
+        const POLL_INTERVAL: Duration = Duration::from_secs(5);
+        let path = cfg.load().path.to_owned();
+        let modified = async || tokio::fs::metadata(&path).await?.modified();
+        let mut last = modified().await.ok();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let current = modified().await.ok();
+            if current == last {
+                continue;
+            }
+            last = current;
+            match BotCfg::read(&path) {
+                Ok(new) => {
+                    info!("Configuration file {} reloaded", path.display());
+                    cfg.store(Arc::new(new));
+                }
+                Err(e) => warn!("Failed to reload configuration, keeping the old one: {e}"),
+            }
+        }
+    }
+
     pub async fn write(&self) -> Result<(), BotError> {
         let json = serenity::json::to_vec_pretty(self)
             .whatever_context::<&str, BotError>("Failed to serialize configuration to JSON")?;
@@ -67,4 +67,21 @@ impl BotDatabase {
         self.db.execute(stmt).await?;
         Ok(())
     }
+
+    /// Return the free pages to the file system, returns `false` if `auto_vacuum` is not
+    /// incremental and nothing was done
+    pub async fn incremental_vacuum(&self) -> Result<bool, BotError> {
+        const INCREMENTAL: i32 = 2;
+        let stmt = Statement::from_string(DbBackend::Sqlite, "PRAGMA auto_vacuum");
+        let mode = match self.db.query_one(stmt).await? {
+            Some(row) => row.try_get_by_index::<i32>(0)?,
+            None => 0,
+        };
+        if mode != INCREMENTAL {
+            return Ok(false);
+        }
+        let stmt = Statement::from_string(DbBackend::Sqlite, "PRAGMA incremental_vacuum");
+        self.db.execute(stmt).await?;
+        Ok(true)
+    }
 }
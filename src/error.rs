@@ -33,6 +33,12 @@ pub enum BotError {
         #[snafu(source(from(serenity::Error, Box::new)))]
         source: Box<serenity::Error>,
     },
+    #[snafu(transparent)]
+    ClewdrError {
+        #[snafu(implicit)]
+        loc: Location,
+        source: crate::clewdr::ClewdrError,
+    },
     #[snafu(whatever, display("{message}"))]
     GenericError {
         message: String,
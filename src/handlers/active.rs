@@ -1,3 +1,4 @@
+use chrono::Utc;
 use serenity::all::*;
 use tracing::warn;
 
@@ -29,52 +30,63 @@ impl EventHandler for ActiveHandler {
         }
     }
 
-    // async fn message_delete(
-    //     &self,
-    //     ctx: Context,
-    //     channel_id: ChannelId,
-    //     message_id: MessageId,
-    //     guild_id: Option<GuildId>,
-    // ) {
-    //     const CIA: ChannelId = ChannelId::new(1382012639714086912);
-    //     let message = if let Some(message) = ctx.to_owned().cache.message(channel_id, message_id) {
-    //         Some(message.to_owned())
-    //     } else {
-    //         None
-    //     };
-    //     let Some(message) = message else {
-    //         return;
-    //     };
-    //     let message = message.to_owned();
-    //     if message.author.bot || message.author.system {
-    //         return;
-    //     }
-    //     let embed = CreateEmbed::default()
-    //         .title("Message Deleted")
-    //         .colour(colours::roles::DARK_RED)
-    //         .author(
-    //             CreateEmbedAuthor::new(message.author.name.to_owned())
-    //                 .icon_url(message.author.face()),
-    //         )
-    //         .field("Channel", channel_id.mention().to_string(), true)
-    //         .field(
-    //             "Guild",
-    //             guild_id
-    //                 .and_then(|id| id.name(ctx.to_owned()))
-    //                 .unwrap_or_else(|| "Unknown".into()),
-    //             true,
-    //         )
-    //         .description(message.content.to_owned())
-    //         .footer(CreateEmbedFooter::new(format!(
-    //             "Message ID: {}",
-    //             message_id
-    //         )))
-    //         .timestamp(message.timestamp);
-    //     if let Err(why) = CIA
-    //         .send_message(ctx, CreateMessage::default().embed(embed))
-    //         .await
-    //     {
-    //         warn!("Error sending message delete notification: {why:?}");
-    //     }
-    // }
+    async fn message_delete(
+        &self,
+        ctx: Context,
+        _channel_id: ChannelId,
+        message_id: MessageId,
+        guild_id: Option<GuildId>,
+    ) {
+        if guild_id.is_some() {
+            mark_deleted(&ctx, &[message_id]).await;
+        }
+    }
+
+    async fn message_delete_bulk(
+        &self,
+        ctx: Context,
+        _channel_id: ChannelId,
+        message_ids: Vec<MessageId>,
+        guild_id: Option<GuildId>,
+    ) {
+        if guild_id.is_some() {
+            mark_deleted(&ctx, &message_ids).await;
+        }
+    }
+
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        _new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        // Embeds resolving for links also come as updates, only count real edits
+        if event.guild_id.is_none() || event.edited_timestamp.is_none() {
+            return;
+        }
+        if let Err(why) = ctx
+            .db()
+            .await
+            .expect("Failed to get database")
+            .message()
+            .mark_edited(event.id)
+            .await
+        {
+            warn!("Error recording message edit: {why:?}");
+        }
+    }
+}
+
+async fn mark_deleted(ctx: &Context, message_ids: &[MessageId]) {
+    if let Err(why) = ctx
+        .db()
+        .await
+        .expect("Failed to get database")
+        .message()
+        .mark_deleted(message_ids, Utc::now())
+        .await
+    {
+        warn!("Error recording message deletion: {why:?}");
+    }
 }
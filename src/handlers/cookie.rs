@@ -1,41 +1,278 @@
-use const_format::formatcp;
-use serenity::{async_trait, model::channel::Message, prelude::*};
+use std::time::Duration;
+
+use chrono::Utc;
+use itertools::Itertools;
+use serenity::{all::*, async_trait};
+use snafu::OptionExt;
 use tracing::warn;
 
-use crate::error::BotError;
+use crate::{
+    clewdr::ClewdrClient,
+    config::GetCfg,
+    database::GetDb,
+    error::BotError,
+    services::{collect_extra_texts, cookie_results_embed, submit_cookies},
+    utils::{SecretAction, redact_secrets, scan_secrets},
+};
+
+const WEBHOOK_NAME: &str = "dog-bot secret scanner";
+const WARNING_LIFETIME: Duration = Duration::from_secs(30);
+const SUBMIT_BUTTON_PREFIX: &str = "submit_leaked_cookie:";
+const PENDING_LIFETIME: chrono::Duration = chrono::Duration::hours(24);
+const EMBED_DESCRIPTION_LIMIT: usize = 4000;
 
 pub struct CookieHandler;
 
+impl CookieHandler {
+    async fn inspect(&self, ctx: Context, msg: Message) {
+        if msg.author.bot || msg.webhook_id.is_some() {
+            return;
+        }
+        let cfg = ctx
+            .cfg()
+            .await
+            .expect("Failed to get bot configuration")
+            .load_full();
+        let extra_texts = collect_extra_texts(&ctx.http, &msg).await;
+        let matches = scan_secrets(&cfg.secret_rules, &msg.content);
+        let found = matches
+            .iter()
+            .map(|m| (m.rule, m.secret.to_owned()))
+            .chain(extra_texts.iter().flat_map(|text| {
+                scan_secrets(&cfg.secret_rules, text)
+                    .into_iter()
+                    .map(|m| (m.rule, m.secret))
+            }))
+            .collect::<Vec<_>>();
+        let Some(action) = found.iter().map(|(rule, _)| rule.action).max() else {
+            return;
+        };
+        let warning = found
+            .iter()
+            .map(|(rule, _)| rule.warning())
+            .unique()
+            .join("\n\n");
+        let redacted = redact_secrets(&msg.content, &matches);
+        let cookies = found
+            .iter()
+            .filter(|(rule, _)| rule.submittable)
+            .map(|(_, secret)| secret.to_owned())
+            .unique()
+            .collect::<Vec<_>>();
+        let submittable = cfg.cookie_endpoint.is_some() && !cookies.is_empty();
+
+        let f = async move || -> Result<(), BotError> {
+            let reply = match action {
+                SecretAction::Warn => msg.reply_ping(&ctx.http, warning).await?,
+                SecretAction::Delete | SecretAction::Redact => {
+                    // Take the secret down first, nothing after this may keep it public
+                    msg.delete(&ctx.http).await?;
+                    let mut notice = Vec::new();
+                    if action == SecretAction::Redact {
+                        if !redacted.trim().is_empty() {
+                            match repost(&ctx, &msg, redacted).await {
+                                Ok(()) => notice.push("您的消息已被删除并以打码形式重新发送"),
+                                Err(e) => warn!("Failed to repost redacted message: {e}"),
+                            }
+                        }
+                        // Held in the database, the DM button outlives restarts
+                        let submittable = submittable && hold(&ctx, &msg, &cookies).await;
+                        match notify_author(&ctx, &msg, submittable).await {
+                            Ok(()) if submittable => {
+                                notice.push("原始内容已私信给您, 可以在私信中一键提交给公益站")
+                            }
+                            Ok(()) => notice.push("原始内容已私信给您"),
+                            Err(e) => warn!("Failed to send redacted message to its author: {e}"),
+                        }
+                    }
+                    let mut content = format!("{} {warning}", msg.author.mention());
+                    if !notice.is_empty() {
+                        content.push_str(&format!("\n{}。", notice.join(", ")));
+                    }
+                    msg.channel_id.say(&ctx.http, content).await?
+                }
+            };
+            tokio::time::sleep(WARNING_LIFETIME).await;
+            Ok(reply.delete(&ctx.http).await?)
+        };
+        if let Err(e) = f().await {
+            warn!("Error handling cookie message: {}", e);
+        }
+    }
+}
+
 #[async_trait]
 impl EventHandler for CookieHandler {
     async fn message(&self, ctx: Context, msg: Message) {
-        const DECLARATION: &str = "我明白公屏发送 Cookie 的风险, Cookie 可能会被滥用，包括用于非 AIRP 用途、使用高消耗模型如 Claude Opus 等。";
-        const COOKIE_PATTERN: &str = "sk-ant-sid01";
-        const WARNING: &str = formatcp!(
-            "❌ 我们不建议在公屏发送 Cookie, 这可能会导致 Cookie 被滥用。请谨慎处理您的 Cookie 信息。\n\
-建议使用 `/submit_cookie`(English)或`/提交曲奇`(中文)命令提交 Cookie 给公益站, 以确保安全和隐私。\n\
-如果您确实需要在公屏发送 Cookie, 请确保您已经了解相关风险, 在你的消息中包含以下声明:\n```{}```",
-            DECLARATION
-        );
+        self.inspect(ctx, msg).await;
+    }
 
-        if msg.content.contains(DECLARATION) {
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        // embed-only updates are link previews, the content that produced them was already scanned
+        if event.content.is_none() && event.attachments.is_none() {
             return;
         }
-        if msg
-            .content
-            .chars()
-            .filter(|c| c.is_alphanumeric() || c == &'-')
-            .collect::<String>()
-            .contains(COOKIE_PATTERN)
-        {
-            let f = async move || -> Result<(), BotError> {
-                let reply = msg.reply_ping(&ctx.http, WARNING).await?;
-                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
-                Ok(reply.delete(&ctx.http).await?)
+        let msg = match new {
+            Some(msg) => msg,
+            None => match ctx.http.get_message(event.channel_id, event.id).await {
+                Ok(msg) => msg,
+                Err(e) => {
+                    warn!("Failed to fetch edited message {}: {e}", event.id);
+                    return;
+                }
+            },
+        };
+        self.inspect(ctx, msg).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Some(component) = interaction.as_message_component() else {
+            return;
+        };
+        let Some(id) = component.data.custom_id.strip_prefix(SUBMIT_BUTTON_PREFIX) else {
+            return;
+        };
+        let f = async || -> Result<(), BotError> {
+            component.defer(&ctx.http).await?;
+            let db = ctx.db().await?;
+            let leaked = match id.parse::<MessageId>() {
+                Ok(id) => {
+                    db.leaked_cookies()
+                        .take(id, Utc::now() - PENDING_LIFETIME)
+                        .await?
+                }
+                Err(_) => None,
             };
-            if let Err(e) = f().await {
-                warn!("Error handling cookie message: {}", e);
-            }
+            let Some(leaked) = leaked else {
+                component
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new()
+                            .content("⌛ 该曲奇已过期或已提交, 请使用 `/提交曲奇` 重新提交。")
+                            .components(vec![]),
+                    )
+                    .await?;
+                return Ok(());
+            };
+            let clewdr = ClewdrClient::from_cfg(&ctx.cfg().await?.load())
+                .whatever_context::<&str, BotError>("Cookie endpoint is not configured")?;
+            let results = submit_cookies(
+                &db,
+                component.user.id,
+                leaked.guild_id(),
+                &clewdr,
+                &leaked.cookies,
+            )
+            .await;
+            component
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .embeds(
+                            component
+                                .message
+                                .embeds
+                                .iter()
+                                .cloned()
+                                .map(CreateEmbed::from)
+                                .chain([cookie_results_embed(&results)])
+                                .collect(),
+                        )
+                        .components(vec![]),
+                )
+                .await?;
+            Ok(())
+        };
+        if let Err(e) = f().await {
+            warn!("Error submitting leaked cookie: {}", e);
+        }
+    }
+}
+
+/// Keep the cookies of `msg` for its author to submit from DM, returns whether they were kept.
+async fn hold(ctx: &Context, msg: &Message, cookies: &[String]) -> bool {
+    let f = async || -> Result<(), BotError> {
+        let db = ctx.db().await?;
+        let leaks = db.leaked_cookies();
+        leaks.expire(Utc::now() - PENDING_LIFETIME).await?;
+        leaks
+            .hold(msg.id, msg.author.id, msg.guild_id, cookies)
+            .await
+    };
+    f().await
+        .inspect_err(|e| warn!("Failed to hold leaked cookies: {e}"))
+        .is_ok()
+}
+
+/// Repost `content` through a webhook in the channel of `msg`, under the author's name and avatar.
+async fn repost(ctx: &Context, msg: &Message, content: String) -> Result<(), BotError> {
+    // webhooks live on the parent channel, threads are addressed when executing
+    let (channel_id, thread_id) = match msg.channel_id.to_channel(ctx).await? {
+        Channel::Guild(c) if c.thread_metadata.is_some() => {
+            (c.parent_id.unwrap_or(c.id), Some(c.id))
         }
+        _ => (msg.channel_id, None),
+    };
+    let bot_id = ctx.cache.current_user().id;
+    let webhook = match channel_id
+        .webhooks(&ctx.http)
+        .await?
+        .into_iter()
+        .find(|w| w.user.as_ref().is_some_and(|u| u.id == bot_id) && w.token.is_some())
+    {
+        Some(webhook) => webhook,
+        None => {
+            channel_id
+                .create_webhook(&ctx.http, CreateWebhook::new(WEBHOOK_NAME))
+                .await?
+        }
+    };
+    let name = msg
+        .member
+        .as_ref()
+        .and_then(|m| m.nick.to_owned())
+        .unwrap_or_else(|| msg.author.display_name().to_owned());
+    let mut execute = ExecuteWebhook::new()
+        .content(content)
+        .username(name)
+        .avatar_url(msg.author.face())
+        .allowed_mentions(CreateAllowedMentions::new());
+    if let Some(thread_id) = thread_id {
+        execute = execute.in_thread(thread_id);
+    }
+    webhook.execute(&ctx.http, false, execute).await?;
+    Ok(())
+}
+
+/// DM the author their original message, with a button to submit the leaked cookies.
+async fn notify_author(ctx: &Context, msg: &Message, submittable: bool) -> Result<(), BotError> {
+    let original = if msg.content.trim().is_empty() {
+        "（敏感信息位于附件、嵌入或转发内容中）".to_owned()
+    } else {
+        msg.content.chars().take(EMBED_DESCRIPTION_LIMIT).collect()
+    };
+    let mut dm = CreateMessage::new().embed(
+        CreateEmbed::new()
+            .title("您的消息包含敏感信息, 已被打码")
+            .description(original)
+            .field("频道", msg.channel_id.mention().to_string(), true)
+            .timestamp(msg.timestamp)
+            .color(0xFF0000),
+    );
+    if submittable {
+        dm = dm.button(
+            CreateButton::new(format!("{SUBMIT_BUTTON_PREFIX}{}", msg.id))
+                .label("提交曲奇给公益站")
+                .emoji('🍪')
+                .style(ButtonStyle::Success),
+        );
     }
+    msg.author.direct_message(ctx, dm).await?;
+    Ok(())
 }
@@ -0,0 +1,36 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use serenity::all::*;
+use tracing::error;
+
+use crate::{config::GetCfg, database::GetDb, services::process_cookie_outbox};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Retries queued cookie submissions in the background.
+#[derive(Default)]
+pub struct CookieOutboxHandler {
+    started: AtomicBool,
+}
+
+#[async_trait]
+impl EventHandler for CookieOutboxHandler {
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tokio::spawn(async move {
+            let db = ctx.db().await.expect("Failed to get database");
+            let cfg = ctx.cfg().await.expect("Failed to get bot configuration");
+            loop {
+                if let Err(e) = process_cookie_outbox(&ctx.http, &db, &cfg.load_full()).await {
+                    error!("Failed to process cookie outbox: {e}");
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use chrono::{FixedOffset, Utc};
+use serenity::all::*;
+use tracing::{error, info};
+
+use crate::{
+    commands::stats::{digest_embed, latest_period},
+    config::{DigestCfg, GetCfg},
+    database::{BotDatabase, GetDb},
+    error::BotError,
+};
+
+const DIGEST_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Posts the activity digest of every configured guild once its period is over.
+#[derive(Default)]
+pub struct DigestHandler {
+    started: AtomicBool,
+}
+
+#[async_trait]
+impl EventHandler for DigestHandler {
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tokio::spawn(async move {
+            let db = ctx.db().await.expect("Failed to get database");
+            let cfg = ctx.cfg().await.expect("Failed to get bot configuration");
+            loop {
+                let cfg = cfg.load_full();
+                match FixedOffset::east_opt(cfg.time_offset) {
+                    Some(offset) => {
+                        for (guild_id, digest) in &cfg.digests {
+                            if let Err(e) = post_digest(&ctx, &db, *guild_id, digest, offset).await
+                            {
+                                error!("Failed to post the activity digest of {guild_id}: {e}");
+                            }
+                        }
+                    }
+                    None => error!("Invalid time offset in the configuration"),
+                }
+                tokio::time::sleep(DIGEST_INTERVAL).await;
+            }
+        });
+    }
+}
+
+/// Post the digest of the latest finished period unless it was posted already.
+///
+/// A digest that fails to post is given back to be retried on the next round.
+async fn post_digest(
+    ctx: &Context,
+    db: &BotDatabase,
+    guild_id: GuildId,
+    digest: &DigestCfg,
+    offset: FixedOffset,
+) -> Result<(), BotError> {
+    let (from, to) = latest_period(digest.period, Utc::now(), offset);
+    if !db.digest().claim(guild_id, to).await? {
+        return Ok(());
+    }
+    let posted = async {
+        let guild_name = guild_id.name(ctx).unwrap_or_else(|| guild_id.to_string());
+        let Some(embed) = digest_embed(db, guild_id, &guild_name, digest.period, from, to).await?
+        else {
+            info!("Skipped the activity digest of {guild_id}, no messages in the period");
+            return Ok(());
+        };
+        digest
+            .channel_id
+            .send_message(ctx, CreateMessage::new().embed(embed))
+            .await?;
+        info!(
+            "Posted the activity digest of {guild_id} to {}",
+            digest.channel_id
+        );
+        Ok::<_, BotError>(())
+    }
+    .await;
+    if posted.is_err() {
+        db.digest().release(guild_id, to).await?;
+    }
+    posted
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use moka::sync::Cache;
+use serenity::all::*;
+use tracing::warn;
+
+use crate::database::GetDb;
+
+const SEEN_CAPACITY: u64 = 10_000;
+/// A user who keeps chatting under the same name is saved again after this long
+const SEEN_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Keeps the names of users and channels from gateway events, so stats render without REST calls
+/// and deleted channels keep their last name.
+pub struct KnownNamesHandler {
+    /// Names of the users saved recently, to skip saving them on every message
+    seen: Cache<UserId, (String, Option<String>)>,
+}
+
+impl Default for KnownNamesHandler {
+    fn default() -> Self {
+        Self {
+            seen: Cache::builder()
+                .max_capacity(SEEN_CAPACITY)
+                .time_to_live(SEEN_LIFETIME)
+                .build(),
+        }
+    }
+}
+
+async fn save_users<'u>(ctx: &Context, users: impl IntoIterator<Item = &'u User>) {
+    let db = ctx.db().await.expect("Failed to get database");
+    if let Err(why) = db.known().save_users(users).await {
+        warn!("Error saving user names: {why:?}");
+    }
+}
+
+async fn save_channels<'c>(ctx: &Context, channels: impl IntoIterator<Item = &'c GuildChannel>) {
+    let db = ctx.db().await.expect("Failed to get database");
+    if let Err(why) = db.known().save_channels(channels).await {
+        warn!("Error saving channel names: {why:?}");
+    }
+}
+
+async fn channel_deleted(ctx: &Context, channel_id: ChannelId) {
+    let db = ctx.db().await.expect("Failed to get database");
+    if let Err(why) = db.known().channel_deleted(channel_id).await {
+        warn!("Error recording channel deletion: {why:?}");
+    }
+}
+
+#[async_trait]
+impl EventHandler for KnownNamesHandler {
+    async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: Option<bool>) {
+        save_channels(&ctx, guild.channels.values().chain(&guild.threads)).await;
+        save_users(&ctx, guild.members.values().map(|m| &m.user)).await;
+    }
+
+    async fn channel_create(&self, ctx: Context, channel: GuildChannel) {
+        save_channels(&ctx, [&channel]).await;
+    }
+
+    async fn category_create(&self, ctx: Context, category: GuildChannel) {
+        save_channels(&ctx, [&category]).await;
+    }
+
+    async fn channel_update(&self, ctx: Context, _old: Option<GuildChannel>, new: GuildChannel) {
+        save_channels(&ctx, [&new]).await;
+    }
+
+    async fn channel_delete(
+        &self,
+        ctx: Context,
+        channel: GuildChannel,
+        _messages: Option<Vec<Message>>,
+    ) {
+        channel_deleted(&ctx, channel.id).await;
+    }
+
+    async fn category_delete(&self, ctx: Context, category: GuildChannel) {
+        channel_deleted(&ctx, category.id).await;
+    }
+
+    async fn thread_create(&self, ctx: Context, thread: GuildChannel) {
+        save_channels(&ctx, [&thread]).await;
+    }
+
+    async fn thread_update(&self, ctx: Context, _old: Option<GuildChannel>, new: GuildChannel) {
+        save_channels(&ctx, [&new]).await;
+    }
+
+    async fn thread_delete(
+        &self,
+        ctx: Context,
+        thread: PartialGuildChannel,
+        _full_thread_data: Option<GuildChannel>,
+    ) {
+        channel_deleted(&ctx, thread.id).await;
+    }
+
+    async fn thread_list_sync(&self, ctx: Context, thread_list_sync: ThreadListSyncEvent) {
+        save_channels(&ctx, &thread_list_sync.threads).await;
+    }
+
+    async fn guild_member_addition(&self, ctx: Context, new_member: Member) {
+        save_users(&ctx, [&new_member.user]).await;
+    }
+
+    async fn guild_member_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Member>,
+        _new: Option<Member>,
+        event: GuildMemberUpdateEvent,
+    ) {
+        save_users(&ctx, [&event.user]).await;
+    }
+
+    async fn guild_members_chunk(&self, ctx: Context, chunk: GuildMembersChunkEvent) {
+        save_users(&ctx, chunk.members.values().map(|m| &m.user)).await;
+    }
+
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.guild_id.is_none() || msg.author.bot || msg.author.system {
+            return;
+        }
+        let names = (
+            msg.author.name.to_owned(),
+            msg.author.global_name.to_owned(),
+        );
+        if self.seen.get(&msg.author.id).as_ref() == Some(&names) {
+            return;
+        }
+        save_users(&ctx, [&msg.author]).await;
+        self.seen.insert(msg.author.id, names);
+    }
+}
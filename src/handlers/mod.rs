@@ -1,11 +1,19 @@
 mod active;
 mod boot;
 mod cookie;
+mod cookie_outbox;
+mod digest;
 mod flush;
+mod known_names;
+mod rollup;
 mod tree_hole;
 
 pub use active::ActiveHandler;
 pub use boot::BootHandler;
 pub use cookie::CookieHandler;
+pub use cookie_outbox::CookieOutboxHandler;
+pub use digest::DigestHandler;
 pub use flush::FlushHandler;
+pub use known_names::KnownNamesHandler;
+pub use rollup::{RollupHandler, prune_messages, retention_cutoff};
 pub use tree_hole::TreeHoleHandler;
@@ -0,0 +1,81 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use chrono::{DateTime, Days, Utc};
+use serenity::all::*;
+use tracing::{error, info};
+
+use crate::{
+    config::{BotCfg, GetCfg},
+    database::{BotDatabase, GetDb},
+    error::BotError,
+    repo::day_start,
+};
+
+const ROLLUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const PRUNE_BATCH_SIZE: u64 = 1000;
+
+/// Rolls up the messages of every finished UTC day into the daily activity tables, then prunes
+/// the raw messages past the retention period.
+#[derive(Default)]
+pub struct RollupHandler {
+    started: AtomicBool,
+}
+
+#[async_trait]
+impl EventHandler for RollupHandler {
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tokio::spawn(async move {
+            let db = ctx.db().await.expect("Failed to get database");
+            let cfg = ctx.cfg().await.expect("Failed to get bot configuration");
+            loop {
+                let yesterday = Utc::now().date_naive() - Days::new(1);
+                match db.rollup().roll_up(yesterday).await {
+                    Ok(0) => {}
+                    Ok(days) => info!("Rolled up message activity of {days} days"),
+                    Err(e) => error!("Failed to roll up message activity: {e}"),
+                }
+                match prune_messages(&db, &cfg.load()).await {
+                    Ok(0) => {}
+                    Ok(rows) => info!("Pruned {rows} messages past the retention period"),
+                    Err(e) => error!("Failed to prune messages: {e}"),
+                }
+                tokio::time::sleep(ROLLUP_INTERVAL).await;
+            }
+        });
+    }
+}
+
+/// The time before which raw messages get pruned, `None` when nothing is pruned.
+///
+/// Only days that are rolled up are pruned, so their history is never lost.
+pub async fn retention_cutoff(
+    db: &BotDatabase,
+    cfg: &BotCfg,
+) -> Result<Option<DateTime<Utc>>, BotError> {
+    let (Some(retention), Some(watermark)) =
+        (cfg.message_retention_days, db.rollup().watermark().await?)
+    else {
+        return Ok(None);
+    };
+    Ok(Some(
+        (Utc::now() - Days::new(retention.into())).min(day_start(watermark + Days::new(1))),
+    ))
+}
+
+/// Delete the raw messages older than `message_retention_days`, returns the number of rows pruned.
+pub async fn prune_messages(db: &BotDatabase, cfg: &BotCfg) -> Result<u64, BotError> {
+    let Some(cutoff) = retention_cutoff(db, cfg).await? else {
+        return Ok(0);
+    };
+    let pruned = db.message().prune(cutoff, PRUNE_BATCH_SIZE).await?;
+    if pruned > 0 && cfg.incremental_vacuum && !db.incremental_vacuum().await? {
+        info!("Skipped incremental vacuum, auto_vacuum is not set to INCREMENTAL");
+    }
+    Ok(pruned)
+}
@@ -2,12 +2,14 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
+pub mod clewdr;
 pub mod commands;
 pub mod config;
 pub mod database;
 pub mod error;
 pub mod handlers;
 mod repo;
+pub mod services;
 pub mod utils;
 
 #[derive(Parser)]
@@ -46,15 +46,20 @@ async fn main() -> Result<(), BotError> {
 
     let db = BotDatabase::new(&Args::parse().db).await?;
     let cfg = Arc::new(ArcSwap::from_pointee(cfg));
+    tokio::spawn(BotCfg::watch(cfg.to_owned()));
 
     Client::builder(&cfg.load().token, intents)
         .type_map_insert::<BotDatabase>(db.to_owned())
         .type_map_insert::<BotCfg>(cfg.to_owned())
         .event_handler(BootHandler)
         .event_handler(CookieHandler)
+        .event_handler(CookieOutboxHandler::default())
+        .event_handler(RollupHandler::default())
+        .event_handler(DigestHandler::default())
         .event_handler(TreeHoleHandler::default())
         .event_handler(FlushHandler)
         .event_handler(ActiveHandler)
+        .event_handler(KnownNamesHandler::default())
         .framework(framework(db, cfg))
         .await?
         .start()
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use entities::backfill_cursors::*;
+use sea_orm::{Set, prelude::*, sea_query::OnConflict};
+use serenity::all::*;
+
+use crate::{database::BotDatabase, error::BotError};
+
+pub type BackfillCursor = Model;
+
+pub struct BackfillRepo<'a>(&'a BotDatabase);
+impl BotDatabase {
+    /// Get a reference to the history backfill cursors
+    pub fn backfill(&self) -> BackfillRepo<'_> {
+        BackfillRepo(self)
+    }
+}
+
+impl BackfillRepo<'_> {
+    /// Get the crawl position of a channel
+    pub async fn cursor(&self, channel_id: ChannelId) -> Result<Option<BackfillCursor>, BotError> {
+        Ok(Entity::find_by_id(channel_id.get() as i64)
+            .one(self.0.inner())
+            .await?)
+    }
+
+    /// Save the crawl position of a channel, `oldest` is the oldest message crawled so far and
+    /// `done` whether the history has been crawled back to `until`
+    pub async fn save(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        oldest: Option<MessageId>,
+        until: DateTime<Utc>,
+        done: bool,
+    ) -> Result<(), BotError> {
+        let cursor = ActiveModel {
+            channel_id: Set(channel_id.get() as i64),
+            guild_id: Set(guild_id.get() as i64),
+            oldest_message_id: Set(oldest.map(|id| id.get() as i64)),
+            until: Set(until.into()),
+            done: Set(done),
+            updated_at: Set(Utc::now().into()),
+        };
+        Entity::insert(cursor)
+            .on_conflict(
+                OnConflict::column(Column::ChannelId)
+                    .update_columns([
+                        Column::OldestMessageId,
+                        Column::Until,
+                        Column::Done,
+                        Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_without_returning(self.0.inner())
+            .await?;
+        Ok(())
+    }
+
+    /// Forget every crawl position, so the next backfill starts over
+    pub async fn clear(&self) -> Result<(), BotError> {
+        Entity::delete_many().exec(self.0.inner()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use chrono::{DateTime, Utc};
+use entities::cookie_submissions::*;
+use sea_orm::{
+    QueryOrder, QuerySelect, Set,
+    prelude::*,
+    sea_query::{Alias, Expr, SimpleExpr},
+};
+use serenity::all::*;
+
+use crate::{
+    database::BotDatabase,
+    error::BotError,
+    utils::{CookieStatus, hash_cookie},
+};
+
+pub type CookieSubmission = Model;
+
+pub struct LedgerRepo<'a>(&'a BotDatabase);
+impl BotDatabase {
+    /// Get a reference to the cookie contribution ledger
+    pub fn cookie_ledger(&self) -> LedgerRepo<'_> {
+        LedgerRepo(self)
+    }
+}
+
+/// Contributions of a single user over a time range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contributor {
+    pub user_id: UserId,
+    pub accepted: u64,
+    pub total: u64,
+}
+
+/// A ledger row for the settled outcome of a submission
+pub(super) fn submission(
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    cookie: &str,
+    status: &CookieStatus,
+) -> ActiveModel {
+    ActiveModel {
+        user_id: Set(user_id.get() as i64),
+        guild_id: Set(guild_id.map(|id| id.get() as i64)),
+        cookie_hash: Set(hash_cookie(cookie)),
+        status: Set(status.code().to_owned()),
+        response: Set(status.detail()),
+        created_at: Set(Utc::now().into()),
+        ..Default::default()
+    }
+}
+
+impl LedgerRepo<'_> {
+    /// Record the settled outcome of a submission, only a hash of the cookie is stored
+    pub async fn record(
+        &self,
+        user_id: UserId,
+        guild_id: Option<GuildId>,
+        cookie: &str,
+        status: &CookieStatus,
+    ) -> Result<(), BotError> {
+        submission(user_id, guild_id, cookie, status)
+            .insert(self.0.inner())
+            .await?;
+        Ok(())
+    }
+
+    /// Get the latest submissions of a user
+    pub async fn history(
+        &self,
+        user_id: UserId,
+        limit: u64,
+    ) -> Result<Vec<CookieSubmission>, BotError> {
+        Ok(Entity::find()
+            .filter(Column::UserId.eq(user_id.get() as i64))
+            .order_by_desc(Column::CreatedAt)
+            .limit(limit)
+            .all(self.0.inner())
+            .await?)
+    }
+
+    /// Rank the contributors of a guild by accepted cookies, users without any are left out
+    pub async fn leaderboard(
+        &self,
+        guild_id: GuildId,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: u64,
+    ) -> Result<Vec<Contributor>, BotError> {
+        const ACCEPTED: &str = "accepted_count";
+        const TOTAL: &str = "total_count";
+        let accepted = Expr::case(
+            Column::Status.eq(CookieStatus::Accepted.code()),
+            Expr::value(1),
+        )
+        .finally(Expr::value(0));
+        Ok(Entity::find()
+            .select_only()
+            .column(Column::UserId)
+            .filter(Self::range(guild_id, from, to))
+            .column_as(Expr::expr(accepted.to_owned()).sum(), ACCEPTED)
+            .column_as(Column::Id.count(), TOTAL)
+            .group_by(Column::UserId)
+            .having(Expr::expr(Expr::expr(accepted).sum()).gt(0))
+            .order_by_desc(Expr::col(Alias::new(ACCEPTED)))
+            .order_by_desc(Expr::col(Alias::new(TOTAL)))
+            .limit(limit)
+            .into_tuple::<(i64, i64, i64)>()
+            .all(self.0.inner())
+            .await?
+            .into_iter()
+            .map(|(user_id, accepted, total)| Contributor {
+                user_id: UserId::new(user_id as u64),
+                accepted: accepted as u64,
+                total: total as u64,
+            })
+            .collect())
+    }
+
+    /// Count the submissions of a guild by status code
+    pub async fn totals(
+        &self,
+        guild_id: GuildId,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(String, u64)>, BotError> {
+        const ALIAS: &str = "status_count";
+        Ok(Entity::find()
+            .select_only()
+            .column(Column::Status)
+            .filter(Self::range(guild_id, from, to))
+            .column_as(Column::Id.count(), ALIAS)
+            .group_by(Column::Status)
+            .order_by_desc(Expr::col(Alias::new(ALIAS)))
+            .into_tuple::<(String, i64)>()
+            .all(self.0.inner())
+            .await?
+            .into_iter()
+            .map(|(status, count)| (status, count as u64))
+            .collect())
+    }
+
+    fn range(
+        guild_id: GuildId,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> SimpleExpr {
+        Column::GuildId
+            .eq(guild_id.get() as i64)
+            .and(from.map_or(SimpleExpr::Value(true.into()), |f| Column::CreatedAt.gte(f)))
+            .and(to.map_or(SimpleExpr::Value(true.into()), |t| Column::CreatedAt.lt(t)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use migration::{Migrator, MigratorTrait};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_leaderboard() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let ledger = db.cookie_ledger();
+        let guild = Some(GuildId::new(1));
+        let (alice, bob, carol) = (UserId::new(10), UserId::new(20), UserId::new(30));
+        for (user, status) in [
+            (alice, CookieStatus::Accepted),
+            (bob, CookieStatus::Accepted),
+            (bob, CookieStatus::Accepted),
+            (alice, CookieStatus::Invalid),
+            (bob, CookieStatus::ServerError("500".into())),
+            (carol, CookieStatus::Duplicate),
+        ] {
+            ledger.record(user, guild, "cookie", &status).await.unwrap();
+        }
+        ledger
+            .record(alice, None, "cookie", &CookieStatus::Accepted)
+            .await
+            .unwrap();
+
+        let board = ledger
+            .leaderboard(GuildId::new(1), None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(
+            board,
+            vec![
+                Contributor {
+                    user_id: bob,
+                    accepted: 2,
+                    total: 3
+                },
+                Contributor {
+                    user_id: alice,
+                    accepted: 1,
+                    total: 2
+                },
+            ]
+        );
+        let totals = ledger.totals(GuildId::new(1), None, None).await.unwrap();
+        assert_eq!(totals[0], ("accepted".to_owned(), 3));
+        let history = ledger.history(alice, 10).await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert_ne!(history[0].cookie_hash, "cookie");
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use entities::cookie_outbox::*;
+use sea_orm::{QueryOrder, QuerySelect, Set, TransactionTrait, prelude::*, sea_query::OnConflict};
+use serenity::all::*;
+
+use super::cookie_ledger::submission;
+use crate::{database::BotDatabase, error::BotError, utils::CookieStatus};
+
+pub type QueuedCookie = Model;
+
+pub struct OutboxRepo<'a>(&'a BotDatabase);
+impl BotDatabase {
+    /// Get a reference to the cookie submission outbox
+    pub fn cookie_outbox(&self) -> OutboxRepo<'_> {
+        OutboxRepo(self)
+    }
+}
+
+impl OutboxRepo<'_> {
+    /// Queue a cookie whose first submission failed, returns `false` if it is already queued
+    pub async fn enqueue(
+        &self,
+        user_id: UserId,
+        guild_id: Option<GuildId>,
+        cookie: &str,
+        error: String,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<bool, BotError> {
+        let queued = ActiveModel {
+            user_id: Set(user_id.get() as i64),
+            guild_id: Set(guild_id.map(|id| id.get() as i64)),
+            cookie: Set(cookie.to_owned()),
+            attempts: Set(1),
+            next_attempt_at: Set(next_attempt_at.into()),
+            last_error: Set(Some(error)),
+            created_at: Set(Utc::now().into()),
+            ..Default::default()
+        };
+        let inserted = Entity::insert(queued)
+            .on_conflict(OnConflict::column(Column::Cookie).do_nothing().to_owned())
+            .exec_without_returning(self.0.inner())
+            .await?;
+        Ok(inserted > 0)
+    }
+
+    /// Get the cookies due for another attempt, oldest first
+    pub async fn due(&self, now: DateTime<Utc>, limit: u64) -> Result<Vec<QueuedCookie>, BotError> {
+        Ok(Entity::find()
+            .filter(Column::NextAttemptAt.lte(now))
+            .order_by_asc(Column::NextAttemptAt)
+            .limit(limit)
+            .all(self.0.inner())
+            .await?)
+    }
+
+    /// Record a failed attempt and schedule the next one
+    pub async fn retry_later(
+        &self,
+        queued: QueuedCookie,
+        error: String,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<(), BotError> {
+        let attempts = queued.attempts + 1;
+        let mut queued = ActiveModel::from(queued);
+        queued.attempts = Set(attempts);
+        queued.last_error = Set(Some(error));
+        queued.next_attempt_at = Set(next_attempt_at.into());
+        queued.update(self.0.inner()).await?;
+        Ok(())
+    }
+
+    /// Remove a cookie from the queue
+    pub async fn remove(&self, id: i32) -> Result<(), BotError> {
+        Entity::delete_by_id(id).exec(self.0.inner()).await?;
+        Ok(())
+    }
+
+    /// Take a cookie off the queue and record how it settled in the ledger, in one transaction
+    pub async fn settle(
+        &self,
+        queued: &QueuedCookie,
+        status: &CookieStatus,
+    ) -> Result<(), BotError> {
+        let txn = self.0.inner().begin().await?;
+        submission(queued.user_id(), queued.guild_id(), &queued.cookie, status)
+            .insert(&txn)
+            .await?;
+        Entity::delete_by_id(queued.id).exec(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Number of queued cookies
+    pub async fn count(&self) -> Result<u64, BotError> {
+        Ok(Entity::find().count(self.0.inner()).await?)
+    }
+
+    /// The queued cookie that will be retried first
+    pub async fn next(&self) -> Result<Option<QueuedCookie>, BotError> {
+        Ok(Entity::find()
+            .order_by_asc(Column::NextAttemptAt)
+            .one(self.0.inner())
+            .await?)
+    }
+
+    /// Make every queued cookie due immediately
+    pub async fn make_all_due(&self, now: DateTime<Utc>) -> Result<u64, BotError> {
+        Ok(Entity::update_many()
+            .col_expr(
+                Column::NextAttemptAt,
+                Expr::value(DateTimeWithTimeZone::from(now)),
+            )
+            .exec(self.0.inner())
+            .await?
+            .rows_affected)
+    }
+
+    /// Drop every queued cookie
+    pub async fn purge(&self) -> Result<u64, BotError> {
+        Ok(Entity::delete_many()
+            .exec(self.0.inner())
+            .await?
+            .rows_affected)
+    }
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use entities::digest_posts::*;
+use sea_orm::{Set, prelude::*, sea_query::OnConflict};
+use serenity::all::*;
+
+use crate::{database::BotDatabase, error::BotError};
+
+pub struct DigestRepo<'a>(&'a BotDatabase);
+impl BotDatabase {
+    /// Get a reference to the posted activity digests
+    pub fn digest(&self) -> DigestRepo<'_> {
+        DigestRepo(self)
+    }
+}
+
+impl DigestRepo<'_> {
+    /// Claim the digest of the period ending at `period_end` before posting it, returns `false`
+    /// if it or a later one was already claimed.
+    ///
+    /// Claiming first means a crash between claiming and posting skips a digest rather than
+    /// posting it twice.
+    pub async fn claim(
+        &self,
+        guild_id: GuildId,
+        period_end: DateTime<Utc>,
+    ) -> Result<bool, BotError> {
+        let post = ActiveModel {
+            guild_id: Set(guild_id.get() as i64),
+            period_end: Set(period_end.into()),
+            posted_at: Set(Utc::now().into()),
+        };
+        let claimed = Entity::insert(post)
+            .on_conflict(
+                OnConflict::column(Column::GuildId)
+                    .update_columns([Column::PeriodEnd, Column::PostedAt])
+                    .action_and_where(
+                        Expr::col((Entity, Column::PeriodEnd)).lt(period_end.fixed_offset()),
+                    )
+                    .to_owned(),
+            )
+            .exec_without_returning(self.0.inner())
+            .await?;
+        Ok(claimed > 0)
+    }
+
+    /// Give back the claim on a digest that failed to post, so it is retried
+    pub async fn release(
+        &self,
+        guild_id: GuildId,
+        period_end: DateTime<Utc>,
+    ) -> Result<(), BotError> {
+        Entity::delete_many()
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(Column::PeriodEnd.eq(period_end.fixed_offset()))
+            .exec(self.0.inner())
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeDelta;
+    use migration::{Migrator, MigratorTrait};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_claim_digest() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let guild = GuildId::new(1);
+        let end = Utc::now();
+        assert!(db.digest().claim(guild, end).await.unwrap());
+        // Claimed once only, an earlier period never comes back after a later one
+        assert!(!db.digest().claim(guild, end).await.unwrap());
+        assert!(
+            !db.digest()
+                .claim(guild, end - TimeDelta::days(1))
+                .await
+                .unwrap()
+        );
+        assert!(db.digest().claim(GuildId::new(2), end).await.unwrap());
+
+        let next = end + TimeDelta::days(1);
+        assert!(db.digest().claim(guild, next).await.unwrap());
+        db.digest().release(guild, next).await.unwrap();
+        assert!(db.digest().claim(guild, next).await.unwrap());
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use entities::{known_channels, known_users};
+use sea_orm::{Set, prelude::*, sea_query::OnConflict};
+use serenity::all::*;
+
+use crate::{database::BotDatabase, error::BotError};
+
+/// Rows per statement, well below the bound variables SQLite allows in one
+const BATCH_SIZE: usize = 500;
+
+pub type KnownUser = known_users::Model;
+pub type KnownChannel = known_channels::Model;
+
+pub struct KnownRepo<'a>(&'a BotDatabase);
+impl BotDatabase {
+    /// Get a reference to the names of the users and channels seen through the gateway
+    pub fn known(&self) -> KnownRepo<'_> {
+        KnownRepo(self)
+    }
+}
+
+impl KnownRepo<'_> {
+    /// Save the current names of some users, marking them as seen now
+    pub async fn save_users<'u>(
+        &self,
+        users: impl IntoIterator<Item = &'u User>,
+    ) -> Result<(), BotError> {
+        let now = Utc::now();
+        let users = users
+            .into_iter()
+            .map(|user| known_users::ActiveModel {
+                user_id: Set(user.id.get() as i64),
+                name: Set(user.name.to_owned()),
+                global_name: Set(user.global_name.to_owned()),
+                last_seen_at: Set(now.into()),
+            })
+            .collect::<Vec<_>>();
+        for batch in users.chunks(BATCH_SIZE) {
+            known_users::Entity::insert_many(batch.to_vec())
+                .on_conflict(
+                    OnConflict::column(known_users::Column::UserId)
+                        .update_columns([
+                            known_users::Column::Name,
+                            known_users::Column::GlobalName,
+                            known_users::Column::LastSeenAt,
+                        ])
+                        .to_owned(),
+                )
+                .exec_without_returning(self.0.inner())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Save the current names and places of some channels or threads, a channel seen again is no
+    /// longer deleted
+    pub async fn save_channels<'c>(
+        &self,
+        channels: impl IntoIterator<Item = &'c GuildChannel>,
+    ) -> Result<(), BotError> {
+        let now = Utc::now();
+        let channels = channels
+            .into_iter()
+            .map(|channel| known_channels::ActiveModel {
+                channel_id: Set(channel.id.get() as i64),
+                guild_id: Set(channel.guild_id.get() as i64),
+                name: Set(channel.name.to_owned()),
+                kind: Set(u8::from(channel.kind).into()),
+                parent_id: Set(channel.parent_id.map(|id| id.get() as i64)),
+                deleted_at: Set(None),
+                updated_at: Set(now.into()),
+            })
+            .collect::<Vec<_>>();
+        for batch in channels.chunks(BATCH_SIZE) {
+            known_channels::Entity::insert_many(batch.to_vec())
+                .on_conflict(
+                    OnConflict::column(known_channels::Column::ChannelId)
+                        .update_columns([
+                            known_channels::Column::GuildId,
+                            known_channels::Column::Name,
+                            known_channels::Column::Kind,
+                            known_channels::Column::ParentId,
+                            known_channels::Column::DeletedAt,
+                            known_channels::Column::UpdatedAt,
+                        ])
+                        .to_owned(),
+                )
+                .exec_without_returning(self.0.inner())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Mark a channel or thread as deleted, its last name is kept
+    pub async fn channel_deleted(&self, channel_id: ChannelId) -> Result<(), BotError> {
+        let now = Utc::now();
+        known_channels::Entity::update_many()
+            .col_expr(
+                known_channels::Column::DeletedAt,
+                Expr::value(now.fixed_offset()),
+            )
+            .col_expr(
+                known_channels::Column::UpdatedAt,
+                Expr::value(now.fixed_offset()),
+            )
+            .filter(known_channels::Column::ChannelId.eq(channel_id.get() as i64))
+            .exec(self.0.inner())
+            .await?;
+        Ok(())
+    }
+
+    /// Get the known users among `user_ids`
+    pub async fn users(&self, user_ids: &[UserId]) -> Result<HashMap<UserId, KnownUser>, BotError> {
+        let mut users = HashMap::new();
+        for batch in user_ids.chunks(BATCH_SIZE) {
+            users.extend(
+                known_users::Entity::find()
+                    .filter(
+                        known_users::Column::UserId.is_in(batch.iter().map(|id| id.get() as i64)),
+                    )
+                    .all(self.0.inner())
+                    .await?
+                    .into_iter()
+                    .map(|user| (user.user_id(), user)),
+            );
+        }
+        Ok(users)
+    }
+
+    /// Get the known channels among `channel_ids`, deleted ones included
+    pub async fn channels(
+        &self,
+        channel_ids: &[ChannelId],
+    ) -> Result<HashMap<ChannelId, KnownChannel>, BotError> {
+        let mut channels = HashMap::new();
+        for batch in channel_ids.chunks(BATCH_SIZE) {
+            channels.extend(
+                known_channels::Entity::find()
+                    .filter(
+                        known_channels::Column::ChannelId
+                            .is_in(batch.iter().map(|id| id.get() as i64)),
+                    )
+                    .all(self.0.inner())
+                    .await?
+                    .into_iter()
+                    .map(|channel| (channel.channel_id(), channel)),
+            );
+        }
+        Ok(channels)
+    }
+
+    /// Get every known channel and thread of a guild, deleted ones included
+    pub async fn guild_channels(&self, guild_id: GuildId) -> Result<Vec<KnownChannel>, BotError> {
+        Ok(known_channels::Entity::find()
+            .filter(known_channels::Column::GuildId.eq(guild_id.get() as i64))
+            .all(self.0.inner())
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use migration::{Migrator, MigratorTrait};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_known_names() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let known = db.known();
+
+        let mut user = User::default();
+        user.id = UserId::new(100);
+        user.name = "alice".into();
+        known.save_users([&user]).await.unwrap();
+        user.global_name = Some("Alice".into());
+        known.save_users([&user]).await.unwrap();
+        let users = known.users(&[user.id, UserId::new(200)]).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[&user.id].display_name(), "Alice");
+
+        let mut channel = GuildChannel::default();
+        channel.id = ChannelId::new(10);
+        channel.guild_id = GuildId::new(1);
+        channel.name = "general".into();
+        channel.parent_id = Some(ChannelId::new(5));
+        known.save_channels([&channel]).await.unwrap();
+        known.channel_deleted(channel.id).await.unwrap();
+        let channels = known.guild_channels(channel.guild_id).await.unwrap();
+        assert_eq!(channels.len(), 1);
+        assert_eq!(channels[0].name, "general");
+        assert_eq!(channels[0].parent_id(), Some(ChannelId::new(5)));
+        assert!(channels[0].deleted_at().is_some());
+
+        // Seen again after being deleted, e.g. an archived thread coming back
+        channel.name = "chat".into();
+        known.save_channels([&channel]).await.unwrap();
+        let channels = known.channels(&[channel.id]).await.unwrap();
+        assert_eq!(channels[&channel.id].name, "chat");
+        assert!(channels[&channel.id].deleted_at().is_none());
+    }
+}
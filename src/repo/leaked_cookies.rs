@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use entities::leaked_cookies::*;
+use sea_orm::{Set, prelude::*, sea_query::OnConflict};
+use serenity::all::*;
+
+use crate::{database::BotDatabase, error::BotError};
+
+pub type LeakedCookie = Model;
+
+pub struct LeakRepo<'a>(&'a BotDatabase);
+impl BotDatabase {
+    /// Get a reference to the cookies held for submission from DM
+    pub fn leaked_cookies(&self) -> LeakRepo<'_> {
+        LeakRepo(self)
+    }
+}
+
+impl LeakRepo<'_> {
+    /// Hold the cookies taken out of a redacted message until its author submits them
+    pub async fn hold(
+        &self,
+        message_id: MessageId,
+        user_id: UserId,
+        guild_id: Option<GuildId>,
+        cookies: &[String],
+    ) -> Result<(), BotError> {
+        let leaked = ActiveModel {
+            message_id: Set(message_id.get() as i64),
+            user_id: Set(user_id.get() as i64),
+            guild_id: Set(guild_id.map(|id| id.get() as i64)),
+            cookies: Set(cookies.join("\n")),
+            created_at: Set(Utc::now().into()),
+        };
+        Entity::insert(leaked)
+            .on_conflict(
+                OnConflict::column(Column::MessageId)
+                    .update_columns([Column::Cookies, Column::CreatedAt])
+                    .to_owned(),
+            )
+            .exec_without_returning(self.0.inner())
+            .await?;
+        Ok(())
+    }
+
+    /// Take the cookies of a redacted message, if they were held since `since`. They are gone
+    /// afterwards either way, so a second press cannot submit them again.
+    pub async fn take(
+        &self,
+        message_id: MessageId,
+        since: DateTime<Utc>,
+    ) -> Result<Option<LeakedCookie>, BotError> {
+        let Some(leaked) = Entity::find_by_id(message_id.get() as i64)
+            .one(self.0.inner())
+            .await?
+        else {
+            return Ok(None);
+        };
+        let taken = Entity::delete_by_id(leaked.message_id)
+            .exec(self.0.inner())
+            .await?
+            .rows_affected;
+        Ok(Some(leaked).filter(|l| taken > 0 && l.created_at() >= since))
+    }
+
+    /// Drop the cookies held before `before`, returns how many messages they came from
+    pub async fn expire(&self, before: DateTime<Utc>) -> Result<u64, BotError> {
+        Ok(Entity::delete_many()
+            .filter(Column::CreatedAt.lt(before))
+            .exec(self.0.inner())
+            .await?
+            .rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use migration::{Migrator, MigratorTrait};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_once() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let leaks = db.leaked_cookies();
+        let (message_id, user_id) = (MessageId::new(1), UserId::new(2));
+        let cookies = vec!["a".to_owned(), "b".to_owned()];
+        leaks
+            .hold(message_id, user_id, None, &cookies)
+            .await
+            .unwrap();
+        leaks
+            .hold(MessageId::new(3), user_id, None, &cookies)
+            .await
+            .unwrap();
+
+        let since = Utc::now() - chrono::Duration::hours(1);
+        let taken = leaks.take(message_id, since).await.unwrap().unwrap();
+        assert_eq!(taken.user_id(), user_id);
+        assert_eq!(taken.cookies(), cookies);
+        assert!(leaks.take(message_id, since).await.unwrap().is_none());
+
+        // Held for too long
+        assert!(
+            leaks
+                .take(MessageId::new(3), Utc::now() + chrono::Duration::hours(1))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(leaks.expire(Utc::now()).await.unwrap(), 0);
+    }
+}
@@ -1,8 +1,16 @@
-use chrono::{DateTime, FixedOffset};
-use entities::messages::*;
-use sea_orm::{QueryOrder, QuerySelect, Set, prelude::*, sea_query::*};
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use entities::{
+    daily_channel_activity as channel_days, daily_user_activity as user_days, messages::*,
+};
+use sea_orm::{
+    DbBackend, QueryOrder, QuerySelect, QueryTrait, Set, TransactionTrait, TryGetableMany,
+    prelude::*, sea_query::*,
+};
 use serenity::all::*;
 
+use super::rollups::day_start;
 use crate::{database::BotDatabase, error::BotError};
 
 pub type MessageRecord = Model;
@@ -31,55 +39,152 @@ impl MsgRepo<'_> {
             guild_id: Set(guild_id.get() as i64),
             channel_id: Set(channel_id.get() as i64),
             timestamp: Set(timestamp.to_utc().into()),
+            ..Default::default()
         };
-        Entity::insert(message)
+        // Starting with the insert takes the write lock, so a day being rolled up either counts
+        // the message itself or is bumped after it, never both
+        let txn = self.0.inner().begin().await?;
+        let inserted = Entity::insert(message)
             .on_conflict(
                 OnConflict::column(Column::MessageId)
                     .do_nothing()
                     .to_owned(),
             )
-            .exec(self.0.inner())
+            .exec_without_returning(&txn)
             .await?;
+        // Keep the rollups of days that were already rolled up in sync with late records
+        let day = timestamp.to_utc().date_naive();
+        let rollup = self.0.rollup();
+        if inserted > 0
+            && rollup
+                .watermark_in(&txn)
+                .await?
+                .is_some_and(|watermark| day <= watermark)
+        {
+            rollup
+                .bump(&txn, guild_id, channel_id, user_id, day)
+                .await?;
+        }
+        txn.commit().await?;
         Ok(())
     }
 
-    /// Get channel statistics for a guild
+    /// Split a time range into the whole days covered by the rollups and the raw rows around them
+    async fn split_range(
+        &self,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+    ) -> Result<SplitRange, BotError> {
+        Ok(SplitRange::new(
+            from.map(|f| f.into().to_utc()),
+            to.map(|t| t.into().to_utc()),
+            self.0.rollup().watermark().await?,
+        ))
+    }
+
+    /// Get channel statistics for a guild, counting deleted messages too
     pub async fn get_channel_stats(
         &self,
         guild_id: GuildId,
         from: Option<impl Into<DateTime<FixedOffset>>>,
         to: Option<impl Into<DateTime<FixedOffset>>>,
     ) -> Result<Vec<(ChannelId, u64)>, BotError> {
-        use sea_orm::sea_query::{Alias, Expr};
-        let filter = Column::GuildId.eq(guild_id.get() as i64);
-        let filter = if let Some(from) = from {
-            filter.and(Column::Timestamp.gte(from.into()))
-        } else {
-            filter
-        };
-        let filter = if let Some(to) = to {
-            filter.and(Column::Timestamp.lt(to.into()))
-        } else {
-            filter
-        };
+        Ok(self
+            .get_channel_activity(guild_id, from, to)
+            .await?
+            .into_iter()
+            .map(|(channel_id, activity)| (channel_id, activity.total))
+            .collect())
+    }
 
-        const ALIAS: &str = "message_count";
-        Ok(Entity::find()
+    /// Get message and deletion counts per channel for a guild, most active first
+    pub async fn get_channel_activity(
+        &self,
+        guild_id: GuildId,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+    ) -> Result<Vec<(ChannelId, Activity)>, BotError> {
+        let split = self.split_range(from, to).await?;
+        let mut counts = Entity::find()
             .select_only()
             .column(Column::ChannelId)
-            .filter(filter)
-            .column_as(Column::MessageId.count(), ALIAS)
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(split.raw.to_owned())
+            .column_as(Column::MessageId.count(), COUNT)
+            .column_as(deleted_sum(), DELETED)
             .group_by(Column::ChannelId)
-            .order_by_desc(Expr::col(Alias::new(ALIAS)))
-            .into_tuple::<(i64, i64)>()
+            .into_tuple::<(i64, i64, i64)>()
             .all(self.0.inner())
-            .await?
+            .await?;
+        if let Some(days) = split.days {
+            counts.extend(
+                channel_days::Entity::find()
+                    .select_only()
+                    .column(channel_days::Column::ChannelId)
+                    .filter(channel_days::Column::GuildId.eq(guild_id.get() as i64))
+                    .filter(days.filter(channel_days::Column::Day))
+                    .column_as(channel_days::Column::MessageCount.sum(), COUNT)
+                    .column_as(channel_days::Column::DeletedCount.sum(), DELETED)
+                    .group_by(channel_days::Column::ChannelId)
+                    .into_tuple::<(i64, i64, i64)>()
+                    .all(self.0.inner())
+                    .await?,
+            );
+        }
+        Ok(merge_counts(counts)
+            .map(|(channel_id, activity)| (ChannelId::new(channel_id as u64), activity))
+            .collect())
+    }
+
+    /// Get message and deletion counts per channel for a guild, most active first, counting only
+    /// the messages of the users `keep` returns `true` for
+    pub async fn get_channel_activity_of(
+        &self,
+        guild_id: GuildId,
+        keep: impl Fn(UserId) -> bool,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+    ) -> Result<Vec<(ChannelId, Activity)>, BotError> {
+        let split = self.split_range(from, to).await?;
+        let mut counts = Entity::find()
+            .select_only()
+            .columns([Column::ChannelId, Column::UserId])
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(split.raw.to_owned())
+            .column_as(Column::MessageId.count(), COUNT)
+            .column_as(deleted_sum(), DELETED)
+            .group_by(Column::ChannelId)
+            .group_by(Column::UserId)
+            .into_tuple::<(i64, i64, i64, i64)>()
+            .all(self.0.inner())
+            .await?;
+        if let Some(days) = split.days {
+            counts.extend(
+                user_days::Entity::find()
+                    .select_only()
+                    .columns([user_days::Column::ChannelId, user_days::Column::UserId])
+                    .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+                    .filter(days.filter(user_days::Column::Day))
+                    .column_as(user_days::Column::MessageCount.sum(), COUNT)
+                    .column_as(user_days::Column::DeletedCount.sum(), DELETED)
+                    .group_by(user_days::Column::ChannelId)
+                    .group_by(user_days::Column::UserId)
+                    .into_tuple::<(i64, i64, i64, i64)>()
+                    .all(self.0.inner())
+                    .await?,
+            );
+        }
+        let counts = counts
             .into_iter()
-            .map(|(channel_id, count)| (ChannelId::new(channel_id as u64), count as u64))
+            .filter(|(_, user_id, ..)| keep(UserId::new(*user_id as u64)))
+            .map(|(channel_id, _, total, deleted)| (channel_id, total, deleted))
+            .collect();
+        Ok(merge_counts(counts)
+            .map(|(channel_id, activity)| (ChannelId::new(channel_id as u64), activity))
             .collect())
     }
 
-    /// Get user statistics for a guild
+    /// Get user statistics for a guild, counting deleted messages too
     pub async fn get_user_stats(
         &self,
         guild_id: GuildId,
@@ -87,33 +192,557 @@ impl MsgRepo<'_> {
         from: Option<impl Into<DateTime<FixedOffset>>>,
         to: Option<impl Into<DateTime<FixedOffset>>>,
     ) -> Result<Vec<(UserId, u64)>, BotError> {
-        use sea_orm::sea_query::{Alias, Expr};
+        Ok(self
+            .get_user_activity(guild_id, channel_ids, from, to)
+            .await?
+            .into_iter()
+            .map(|(user_id, activity)| (user_id, activity.total))
+            .collect())
+    }
 
-        const ALIAS: &str = "message_count";
-        Ok(Entity::find()
+    /// Get message and deletion counts per user for a guild, most active first
+    pub async fn get_user_activity(
+        &self,
+        guild_id: GuildId,
+        channel_ids: Option<&[ChannelId]>,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+    ) -> Result<Vec<(UserId, Activity)>, BotError> {
+        let split = self.split_range(from, to).await?;
+        let mut counts = Entity::find()
             .select_only()
             .column(Column::UserId)
             .filter(Column::GuildId.eq(guild_id.get() as i64))
             .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
                 Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
             }))
-            .filter(from.map_or(SimpleExpr::Value(true.into()), |f| {
-                Column::Timestamp.gte(f.into())
-            }))
-            .filter(to.map_or(SimpleExpr::Value(true.into()), |t| {
-                Column::Timestamp.lt(t.into())
+            .filter(split.raw.to_owned())
+            .column_as(Column::MessageId.count(), COUNT)
+            .column_as(deleted_sum(), DELETED)
+            .group_by(Column::UserId)
+            .into_tuple::<(i64, i64, i64)>()
+            .all(self.0.inner())
+            .await?;
+        if let Some(days) = split.days {
+            counts.extend(
+                user_days::Entity::find()
+                    .select_only()
+                    .column(user_days::Column::UserId)
+                    .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+                    .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                        user_days::Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
+                    }))
+                    .filter(days.filter(user_days::Column::Day))
+                    .column_as(user_days::Column::MessageCount.sum(), COUNT)
+                    .column_as(user_days::Column::DeletedCount.sum(), DELETED)
+                    .group_by(user_days::Column::UserId)
+                    .into_tuple::<(i64, i64, i64)>()
+                    .all(self.0.inner())
+                    .await?,
+            );
+        }
+        Ok(merge_counts(counts)
+            .map(|(user_id, activity)| (UserId::new(user_id as u64), activity))
+            .collect())
+    }
+
+    /// A page of the ranking of `get_channel_activity` or `get_user_activity`, summed and ordered
+    /// by the database so a large ranking can be read a page at a time
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_activity_page(
+        &self,
+        by: ActivityBy,
+        guild_id: GuildId,
+        channel_ids: Option<&[ChannelId]>,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<(u64, Activity)>, BotError> {
+        let split = self.split_range(from, to).await?;
+        let page = Query::select()
+            .column(Alias::new(ID))
+            .expr_as(Expr::col(Alias::new(COUNT)).sum(), Alias::new(COUNT))
+            .expr_as(Expr::col(Alias::new(DELETED)).sum(), Alias::new(DELETED))
+            .from_subquery(
+                activity_parts(by, guild_id, channel_ids, &split),
+                Alias::new(PARTS),
+            )
+            .group_by_col(Alias::new(ID))
+            .order_by(Alias::new(COUNT), Order::Desc)
+            .order_by(Alias::new(ID), Order::Asc)
+            .offset(offset)
+            .limit(limit)
+            .to_owned();
+        let rows = self
+            .0
+            .inner()
+            .query_all(DbBackend::Sqlite.build(&page))
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let (id, total, deleted) = <(i64, i64, i64)>::try_get_many_by_index(row)?;
+                Ok((
+                    id as u64,
+                    Activity {
+                        total: total as u64,
+                        deleted: deleted as u64,
+                    },
+                ))
+            })
+            .collect::<Result<_, DbErr>>()
+            .map_err(Into::into)
+    }
+
+    /// Messages and deletions in a guild altogether, the sum of every ranking over the same range
+    pub async fn get_activity_sum(
+        &self,
+        guild_id: GuildId,
+        channel_ids: Option<&[ChannelId]>,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+    ) -> Result<Activity, BotError> {
+        let split = self.split_range(from, to).await?;
+        let parts = activity_parts(ActivityBy::Channel, guild_id, channel_ids, &split);
+        let sum = Query::select()
+            .expr(Expr::col(Alias::new(COUNT)).sum())
+            .expr(Expr::col(Alias::new(DELETED)).sum())
+            .from_subquery(parts, Alias::new(PARTS))
+            .to_owned();
+        let (total, deleted) = match self
+            .0
+            .inner()
+            .query_one(DbBackend::Sqlite.build(&sum))
+            .await?
+        {
+            Some(row) => {
+                <(Option<i64>, Option<i64>)>::try_get_many_by_index(&row).map_err(DbErr::from)?
+            }
+            None => (None, None),
+        };
+        Ok(Activity {
+            total: total.unwrap_or_default() as u64,
+            deleted: deleted.unwrap_or_default() as u64,
+        })
+    }
+
+    /// Get message and deletion counts per channel for a single user in a guild, most active first
+    pub async fn get_user_channel_activity(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+    ) -> Result<Vec<(ChannelId, Activity)>, BotError> {
+        let split = self.split_range(from, to).await?;
+        let mut counts = Entity::find()
+            .select_only()
+            .column(Column::ChannelId)
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(Column::UserId.eq(user_id.get() as i64))
+            .filter(split.raw.to_owned())
+            .column_as(Column::MessageId.count(), COUNT)
+            .column_as(deleted_sum(), DELETED)
+            .group_by(Column::ChannelId)
+            .into_tuple::<(i64, i64, i64)>()
+            .all(self.0.inner())
+            .await?;
+        if let Some(days) = split.days {
+            counts.extend(
+                user_days::Entity::find()
+                    .select_only()
+                    .column(user_days::Column::ChannelId)
+                    .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+                    .filter(user_days::Column::UserId.eq(user_id.get() as i64))
+                    .filter(days.filter(user_days::Column::Day))
+                    .column_as(user_days::Column::MessageCount.sum(), COUNT)
+                    .column_as(user_days::Column::DeletedCount.sum(), DELETED)
+                    .group_by(user_days::Column::ChannelId)
+                    .into_tuple::<(i64, i64, i64)>()
+                    .all(self.0.inner())
+                    .await?,
+            );
+        }
+        Ok(merge_counts(counts)
+            .map(|(channel_id, activity)| (ChannelId::new(channel_id as u64), activity))
+            .collect())
+    }
+
+    /// Time of the first recorded message of a user in a guild, the start of its day when the
+    /// raw message was already pruned
+    pub async fn first_seen(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<Option<DateTime<Utc>>, BotError> {
+        let raw = Entity::find()
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(Column::UserId.eq(user_id.get() as i64))
+            .order_by_asc(Column::Timestamp)
+            .one(self.0.inner())
+            .await?
+            .map(|m| m.timestamp());
+        let rolled_up = user_days::Entity::find()
+            .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+            .filter(user_days::Column::UserId.eq(user_id.get() as i64))
+            .order_by_asc(user_days::Column::Day)
+            .one(self.0.inner())
+            .await?
+            .map(|d| d.day);
+        Ok(earliest(raw, rolled_up))
+    }
+
+    /// Time of the first recorded message of every user in a guild, optionally limited to some
+    /// channels, the start of its day when the raw message was already pruned
+    pub async fn first_seen_by_user(
+        &self,
+        guild_id: GuildId,
+        channel_ids: Option<&[ChannelId]>,
+    ) -> Result<HashMap<UserId, DateTime<Utc>>, BotError> {
+        let raw = Entity::find()
+            .select_only()
+            .column(Column::UserId)
+            .column_as(Column::Timestamp.min(), FIRST)
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
             }))
-            .column_as(Column::MessageId.count(), ALIAS)
             .group_by(Column::UserId)
-            .order_by_desc(Expr::col(Alias::new(ALIAS)))
-            .into_tuple::<(i64, i64)>()
+            .into_tuple::<(i64, DateTimeWithTimeZone)>()
             .all(self.0.inner())
             .await?
             .into_iter()
-            .map(|(user_id, count)| (UserId::new(user_id as u64), count as u64))
+            .map(|(user_id, first)| (user_id, first.to_utc()))
+            .collect::<HashMap<_, _>>();
+        let rolled_up = user_days::Entity::find()
+            .select_only()
+            .column(user_days::Column::UserId)
+            .column_as(user_days::Column::Day.min(), FIRST)
+            .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+            .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                user_days::Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
+            }))
+            .group_by(user_days::Column::UserId)
+            .into_tuple::<(i64, NaiveDate)>()
+            .all(self.0.inner())
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        Ok(raw
+            .keys()
+            .chain(rolled_up.keys())
+            .filter_map(|&user_id| {
+                let first = earliest(raw.get(&user_id).copied(), rolled_up.get(&user_id).copied())?;
+                Some((UserId::new(user_id as u64), first))
+            })
+            .collect())
+    }
+
+    /// Get the distinct users who sent messages in a guild, optionally limited to some channels
+    pub async fn get_active_users(
+        &self,
+        guild_id: GuildId,
+        channel_ids: Option<&[ChannelId]>,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+    ) -> Result<HashSet<UserId>, BotError> {
+        let split = self.split_range(from, to).await?;
+        let mut users = Entity::find()
+            .select_only()
+            .column(Column::UserId)
+            .distinct()
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
+            }))
+            .filter(split.raw.to_owned())
+            .into_tuple::<i64>()
+            .all(self.0.inner())
+            .await?;
+        if let Some(days) = split.days {
+            users.extend(
+                user_days::Entity::find()
+                    .select_only()
+                    .column(user_days::Column::UserId)
+                    .distinct()
+                    .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+                    .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                        user_days::Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
+                    }))
+                    .filter(days.filter(user_days::Column::Day))
+                    .into_tuple::<i64>()
+                    .all(self.0.inner())
+                    .await?,
+            );
+        }
+        Ok(users
+            .into_iter()
+            .map(|user_id| UserId::new(user_id as u64))
             .collect())
     }
 
+    /// Get the number of distinct users who sent messages per UTC day in a guild, optionally
+    /// limited to some channels, oldest first. Days without messages are left out.
+    pub async fn get_daily_active_users(
+        &self,
+        guild_id: GuildId,
+        channel_ids: Option<&[ChannelId]>,
+        from: Option<impl Into<DateTime<FixedOffset>>>,
+        to: Option<impl Into<DateTime<FixedOffset>>>,
+    ) -> Result<Vec<(NaiveDate, u64)>, BotError> {
+        let split = self.split_range(from, to).await?;
+        let day = SimpleExpr::from(
+            Func::cust(Alias::new("strftime"))
+                .arg("%Y-%m-%d")
+                .arg(Expr::col(Column::Timestamp)),
+        );
+        let mut counts = Entity::find()
+            .select_only()
+            .column_as(day, DAY)
+            .column_as(Expr::col(Column::UserId).count_distinct(), COUNT)
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
+            }))
+            .filter(split.raw.to_owned())
+            .group_by(Expr::col(Alias::new(DAY)))
+            .into_tuple::<(NaiveDate, i64)>()
+            .all(self.0.inner())
+            .await?;
+        // The rollups and the raw rows never serve the same day, so no user is counted twice
+        if let Some(days) = split.days {
+            counts.extend(
+                user_days::Entity::find()
+                    .select_only()
+                    .column(user_days::Column::Day)
+                    .column_as(Expr::col(user_days::Column::UserId).count_distinct(), COUNT)
+                    .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+                    .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                        user_days::Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
+                    }))
+                    .filter(days.filter(user_days::Column::Day))
+                    .group_by(user_days::Column::Day)
+                    .into_tuple::<(NaiveDate, i64)>()
+                    .all(self.0.inner())
+                    .await?,
+            );
+        }
+        let mut counts = counts
+            .into_iter()
+            .map(|(day, count)| (day, count as u64))
+            .collect::<Vec<_>>();
+        counts.sort_unstable();
+        Ok(counts)
+    }
+
+    /// Time of the latest recorded message of a user in a guild, the start of its day when the
+    /// raw message was already pruned
+    pub async fn last_seen(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<Option<DateTime<Utc>>, BotError> {
+        let raw = Entity::find()
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(Column::UserId.eq(user_id.get() as i64))
+            .order_by_desc(Column::Timestamp)
+            .one(self.0.inner())
+            .await?
+            .map(|m| m.timestamp());
+        let rolled_up = user_days::Entity::find()
+            .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+            .filter(user_days::Column::UserId.eq(user_id.get() as i64))
+            .order_by_desc(user_days::Column::Day)
+            .one(self.0.inner())
+            .await?
+            .map(|d| day_start(d.day));
+        // A message is never earlier than the start of its own day
+        Ok(raw.max(rolled_up))
+    }
+
+    /// Get the messages sent per UTC hour in a guild from the raw messages only, optionally
+    /// limited to some channels or a user, oldest first.
+    pub async fn get_raw_hourly_activity(
+        &self,
+        guild_id: GuildId,
+        channel_ids: Option<&[ChannelId]>,
+        user_id: Option<UserId>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(DateTime<Utc>, u64)>, BotError> {
+        let hour = Func::cust(Alias::new("strftime"))
+            .arg("%Y-%m-%d %H")
+            .arg(Expr::col(Column::Timestamp));
+        Ok(Entity::find()
+            .select_only()
+            .column_as(SimpleExpr::from(hour), HOUR)
+            .filter(Column::GuildId.eq(guild_id.get() as i64))
+            .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
+            }))
+            .filter(user_id.map_or(SimpleExpr::Value(true.into()), |u| {
+                Column::UserId.eq(u.get() as i64)
+            }))
+            .filter(SplitRange::between(from, to))
+            .column_as(Column::MessageId.count(), COUNT)
+            .group_by(Expr::col(Alias::new(HOUR)))
+            .order_by_asc(Expr::col(Alias::new(HOUR)))
+            .into_tuple::<(String, i64)>()
+            .all(self.0.inner())
+            .await?
+            .into_iter()
+            .map(|(hour, count)| {
+                let hour = NaiveDateTime::parse_from_str(&format!("{hour}:00"), "%Y-%m-%d %H:%M")
+                    .map_err(|e| DbErr::Type(format!("Invalid hour {hour}: {e}")))?;
+                Ok((hour.and_utc(), count as u64))
+            })
+            .collect::<Result<Vec<_>, DbErr>>()?)
+    }
+
+    /// Get the messages sent per UTC hour in a guild, optionally limited to some channels or a
+    /// user, oldest first.
+    ///
+    /// Days whose raw messages were pruned are served by their rollups, spread evenly over the
+    /// hours of the day.
+    pub async fn get_hourly_activity(
+        &self,
+        guild_id: GuildId,
+        channel_ids: Option<&[ChannelId]>,
+        user_id: Option<UserId>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<HourlyActivity, BotError> {
+        let oldest = Entity::find()
+            .order_by_asc(Column::Timestamp)
+            .one(self.0.inner())
+            .await?
+            .map(|m| m.timestamp().date_naive());
+        let watermark = self.0.rollup().watermark().await?;
+        // First day read from the raw messages, the oldest one may have been pruned partially
+        let boundary = match (oldest, watermark) {
+            (Some(oldest), Some(watermark)) if oldest <= watermark => oldest.succ_opt(),
+            (Some(oldest), _) => Some(oldest),
+            (None, watermark) => watermark.and_then(|w| w.succ_opt()),
+        };
+        let mut counts = self
+            .get_raw_hourly_activity(
+                guild_id,
+                channel_ids,
+                user_id,
+                from.max(boundary.map(day_start)),
+                to,
+            )
+            .await?;
+        let pruned = boundary
+            .filter(|_| watermark.is_some())
+            .map(|end| DayRange {
+                start: from.map(|f| f.date_naive()),
+                end: to.map_or(end, |t| {
+                    end.min(t.date_naive().succ_opt().expect("date overflow"))
+                }),
+            })
+            .filter(|days| days.start.is_none_or(|start| start < days.end));
+        if let Some(days) = pruned {
+            let rollups = match user_id {
+                Some(user_id) => {
+                    user_days::Entity::find()
+                        .select_only()
+                        .column(user_days::Column::Day)
+                        .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+                        .filter(user_days::Column::UserId.eq(user_id.get() as i64))
+                        .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                            user_days::Column::ChannelId.is_in(c.iter().map(|id| id.get() as i64))
+                        }))
+                        .filter(days.filter(user_days::Column::Day))
+                        .column_as(user_days::Column::MessageCount.sum(), COUNT)
+                        .group_by(user_days::Column::Day)
+                        .into_tuple::<(NaiveDate, i64)>()
+                        .all(self.0.inner())
+                        .await?
+                }
+                None => {
+                    channel_days::Entity::find()
+                        .select_only()
+                        .column(channel_days::Column::Day)
+                        .filter(channel_days::Column::GuildId.eq(guild_id.get() as i64))
+                        .filter(channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+                            channel_days::Column::ChannelId
+                                .is_in(c.iter().map(|id| id.get() as i64))
+                        }))
+                        .filter(days.filter(channel_days::Column::Day))
+                        .column_as(channel_days::Column::MessageCount.sum(), COUNT)
+                        .group_by(channel_days::Column::Day)
+                        .into_tuple::<(NaiveDate, i64)>()
+                        .all(self.0.inner())
+                        .await?
+                }
+            };
+            counts.extend(
+                rollups
+                    .into_iter()
+                    .flat_map(|(day, count)| spread_day(day, count as u64)),
+            );
+        }
+        counts.sort_unstable();
+        Ok(HourlyActivity {
+            counts,
+            spread_until: pruned.map(|days| day_start(days.end)),
+        })
+    }
+
+    /// Mark messages as deleted, returns the number of recorded messages affected
+    pub async fn mark_deleted(
+        &self,
+        message_ids: &[MessageId],
+        deleted_at: DateTime<Utc>,
+    ) -> Result<u64, BotError> {
+        // Writing first, like `record`, keeps a roll up from counting the deletion twice
+        let txn = self.0.inner().begin().await?;
+        let update = Query::update()
+            .table(Entity)
+            .value(Column::DeletedAt, DateTimeWithTimeZone::from(deleted_at))
+            .and_where(Column::MessageId.is_in(message_ids.iter().map(|id| id.get() as i64)))
+            .and_where(Column::DeletedAt.is_null())
+            .returning_all()
+            .to_owned();
+        let deleted = Entity::find()
+            .from_raw_sql(txn.get_database_backend().build(&update))
+            .all(&txn)
+            .await?;
+        // Days already rolled up would keep counting them otherwise
+        let rollup = self.0.rollup();
+        if !deleted.is_empty()
+            && let Some(watermark) = rollup.watermark_in(&txn).await?
+        {
+            for message in deleted
+                .iter()
+                .filter(|m| m.timestamp().date_naive() <= watermark)
+            {
+                rollup
+                    .bump_deleted(
+                        &txn,
+                        message.guild_id(),
+                        message.channel_id(),
+                        message.user_id(),
+                        message.timestamp().date_naive(),
+                    )
+                    .await?;
+            }
+        }
+        txn.commit().await?;
+        Ok(deleted.len() as u64)
+    }
+
+    /// Count an edit of a message
+    pub async fn mark_edited(&self, message_id: MessageId) -> Result<(), BotError> {
+        Entity::update_many()
+            .col_expr(Column::EditedCount, Expr::col(Column::EditedCount).add(1))
+            .filter(Column::MessageId.eq(message_id.get() as i64))
+            .exec(self.0.inner())
+            .await?;
+        Ok(())
+    }
+
     /// Get message records for a specific user in a guild
     pub async fn get_user_messages(
         &self,
@@ -131,13 +760,245 @@ impl MsgRepo<'_> {
             .await?)
     }
 
-    /// Clear all message data (dangerous operation)
+    /// Delete the raw messages older than `before` in batches of `batch_size`, returns the number
+    /// of rows deleted
+    pub async fn prune(&self, before: DateTime<Utc>, batch_size: u64) -> Result<u64, BotError> {
+        let mut pruned = 0;
+        loop {
+            let batch = Entity::find()
+                .select_only()
+                .column(Column::MessageId)
+                .filter(Column::Timestamp.lt(before.fixed_offset()))
+                .limit(batch_size)
+                .into_tuple::<i64>()
+                .all(self.0.inner())
+                .await?;
+            if batch.is_empty() {
+                return Ok(pruned);
+            }
+            pruned += Entity::delete_many()
+                .filter(Column::MessageId.is_in(batch))
+                .exec(self.0.inner())
+                .await?
+                .rows_affected;
+            // Let the gateway handlers write between batches
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Clear all message data, its rollups and the backfill cursors (dangerous operation)
     pub async fn nuke(&self) -> Result<(), BotError> {
         Entity::delete_many().exec(self.0.inner()).await?;
+        self.0.rollup().clear().await?;
+        self.0.backfill().clear().await?;
         Ok(())
     }
 }
 
+const COUNT: &str = "message_count";
+const HOUR: &str = "hour";
+const DAY: &str = "day";
+const FIRST: &str = "first_seen";
+const DELETED: &str = "deleted_count";
+const ID: &str = "id";
+const PARTS: &str = "parts";
+
+/// What a ranking is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityBy {
+    Channel,
+    User,
+}
+
+/// Messages sent by a user or in a channel, `deleted` of which were deleted afterwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Activity {
+    pub total: u64,
+    pub deleted: u64,
+}
+
+impl Activity {
+    /// Messages that were not deleted
+    pub fn kept(&self) -> u64 {
+        self.total - self.deleted
+    }
+
+    /// Share of the messages that were deleted, in percent
+    pub fn deletion_rate(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.deleted as f64 * 100.0 / self.total as f64
+    }
+}
+
+fn deleted_sum() -> SimpleExpr {
+    Expr::expr(Expr::case(Column::DeletedAt.is_not_null(), Expr::value(1)).finally(Expr::value(0)))
+        .sum()
+}
+
+/// The counts of the raw rows and of the rollups in `split`, each grouped by `by`, to be summed
+/// together
+fn activity_parts(
+    by: ActivityBy,
+    guild_id: GuildId,
+    channel_ids: Option<&[ChannelId]>,
+    split: &SplitRange,
+) -> SelectStatement {
+    let in_channels = |column: SimpleExpr| {
+        channel_ids.map_or(SimpleExpr::Value(true.into()), |c| {
+            column.is_in(c.iter().map(|id| id.get() as i64))
+        })
+    };
+    let mut raw = Entity::find()
+        .select_only()
+        .column_as(
+            match by {
+                ActivityBy::Channel => Column::ChannelId,
+                ActivityBy::User => Column::UserId,
+            },
+            ID,
+        )
+        .column_as(Column::MessageId.count(), COUNT)
+        .column_as(deleted_sum(), DELETED)
+        .filter(Column::GuildId.eq(guild_id.get() as i64))
+        .filter(in_channels(Expr::col(Column::ChannelId).into()))
+        .filter(split.raw.to_owned())
+        .group_by(Expr::col(Alias::new(ID)))
+        .into_query();
+    if let Some(days) = split.days {
+        raw.union(
+            UnionType::All,
+            match by {
+                ActivityBy::Channel => channel_days::Entity::find()
+                    .select_only()
+                    .column_as(channel_days::Column::ChannelId, ID)
+                    .column_as(channel_days::Column::MessageCount.sum(), COUNT)
+                    .column_as(channel_days::Column::DeletedCount.sum(), DELETED)
+                    .filter(channel_days::Column::GuildId.eq(guild_id.get() as i64))
+                    .filter(in_channels(
+                        Expr::col(channel_days::Column::ChannelId).into(),
+                    ))
+                    .filter(days.filter(channel_days::Column::Day))
+                    .group_by(Expr::col(Alias::new(ID)))
+                    .into_query(),
+                ActivityBy::User => user_days::Entity::find()
+                    .select_only()
+                    .column_as(user_days::Column::UserId, ID)
+                    .column_as(user_days::Column::MessageCount.sum(), COUNT)
+                    .column_as(user_days::Column::DeletedCount.sum(), DELETED)
+                    .filter(user_days::Column::GuildId.eq(guild_id.get() as i64))
+                    .filter(in_channels(Expr::col(user_days::Column::ChannelId).into()))
+                    .filter(days.filter(user_days::Column::Day))
+                    .group_by(Expr::col(Alias::new(ID)))
+                    .into_query(),
+            },
+        );
+    }
+    raw
+}
+
+/// Messages sent per UTC hour
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HourlyActivity {
+    /// Oldest first, hours without messages left out
+    pub counts: Vec<(DateTime<Utc>, u64)>,
+    /// End of the days only known from their rollups, whose hours are an even spread of the day
+    pub spread_until: Option<DateTime<Utc>>,
+}
+
+/// The count of a rolled up day spread evenly over its hours, empty hours left out
+fn spread_day(day: NaiveDate, count: u64) -> impl Iterator<Item = (DateTime<Utc>, u64)> {
+    let start = day_start(day);
+    (0..24).filter_map(move |hour: u64| {
+        let share = (hour + 1) * count / 24 - hour * count / 24;
+        (share > 0).then(|| (start + chrono::Duration::hours(hour as i64), share))
+    })
+}
+
+/// Whole UTC days `[start, end)` served by the rollups, unbounded below if `start` is `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DayRange {
+    start: Option<NaiveDate>,
+    end: NaiveDate,
+}
+
+impl DayRange {
+    fn filter(self, column: impl ColumnTrait) -> SimpleExpr {
+        column.lt(self.end).and(
+            self.start
+                .map_or(SimpleExpr::Value(true.into()), |s| column.gte(s)),
+        )
+    }
+}
+
+/// A time range split into the whole days read from the rollups and the raw rows at its edges.
+#[derive(Debug, Clone)]
+struct SplitRange {
+    days: Option<DayRange>,
+    raw: SimpleExpr,
+}
+
+impl SplitRange {
+    fn new(
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        watermark: Option<NaiveDate>,
+    ) -> Self {
+        let days = watermark.and_then(|watermark| {
+            let after_watermark = watermark.succ_opt()?;
+            let start = from.map(|f| match f.time() {
+                NaiveTime::MIN => f.date_naive(),
+                _ => f.date_naive().succ_opt().expect("date overflow"),
+            });
+            let end = to.map_or(after_watermark, |t| t.date_naive().min(after_watermark));
+            start
+                .is_none_or(|start| start < end)
+                .then_some(DayRange { start, end })
+        });
+        let raw = match days {
+            None => Self::between(from, to),
+            Some(DayRange { start, end }) => start
+                .map_or(SimpleExpr::Value(false.into()), |start| {
+                    Self::between(from, Some(day_start(start)))
+                })
+                .or(Self::between(Some(day_start(end)), to)),
+        };
+        Self { days, raw }
+    }
+
+    fn between(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> SimpleExpr {
+        from.map_or(SimpleExpr::Value(true.into()), |f| {
+            Column::Timestamp.gte(f.fixed_offset())
+        })
+        .and(to.map_or(SimpleExpr::Value(true.into()), |t| {
+            Column::Timestamp.lt(t.fixed_offset())
+        }))
+    }
+}
+
+/// The earlier of a raw message time and the start of a rolled up day
+fn earliest(raw: Option<DateTime<Utc>>, rolled_up: Option<NaiveDate>) -> Option<DateTime<Utc>> {
+    match (raw, rolled_up) {
+        // The rollup of the first day starts before the message itself
+        (Some(raw), Some(rolled_up)) if raw.date_naive() == rolled_up => Some(raw),
+        (raw, rolled_up) => raw.into_iter().chain(rolled_up.map(day_start)).min(),
+    }
+}
+
+/// Sum partial counts per id, most active first.
+fn merge_counts(counts: Vec<(i64, i64, i64)>) -> impl Iterator<Item = (i64, Activity)> {
+    let mut merged = HashMap::<i64, Activity>::new();
+    for (id, total, deleted) in counts {
+        let activity = merged.entry(id).or_default();
+        activity.total += total as u64;
+        activity.deleted += deleted as u64;
+    }
+    let mut merged = merged.into_iter().collect::<Vec<_>>();
+    merged.sort_unstable_by(|(a_id, a), (b_id, b)| b.total.cmp(&a.total).then(a_id.cmp(b_id)));
+    merged.into_iter()
+}
+
 #[cfg(test)]
 mod test {
     use migration::{Migrator, MigratorTrait, SchemaManager};
@@ -187,4 +1048,317 @@ mod test {
         assert_eq!(channel_stats[0].0, channel_id);
         assert_eq!(channel_stats[0].1, 1);
     }
+
+    #[tokio::test]
+    async fn test_stats_with_rollups() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let service = db.message();
+        let (guild, ch1, ch2) = (GuildId::new(1), ChannelId::new(10), ChannelId::new(20));
+        let (alice, bob) = (UserId::new(100), UserId::new(200));
+        let today = day_start(Utc::now().date_naive());
+        let at = |days: i64, hours: i64| {
+            today - chrono::Duration::days(days) + chrono::Duration::hours(hours)
+        };
+        let messages = [
+            (at(5, 10), ch1, alice),
+            (at(5, 23), ch1, alice),
+            (at(3, 12), ch2, bob),
+            (at(1, 3), ch1, bob),
+            (at(0, 0), ch2, alice),
+        ];
+        for (i, (time, channel, user)) in messages.into_iter().enumerate() {
+            service
+                .record(
+                    MessageId::new(i as u64 + 1),
+                    user,
+                    guild,
+                    channel,
+                    time.into(),
+                )
+                .await
+                .unwrap();
+        }
+        let check = async || {
+            let all = service
+                .get_channel_stats(guild, None::<DateTime<Utc>>, None::<DateTime<Utc>>)
+                .await
+                .unwrap();
+            assert_eq!(all, vec![(ch1, 3), (ch2, 2)]);
+            let edges = service
+                .get_channel_stats(guild, Some(at(5, 12)), Some(at(1, 6)))
+                .await
+                .unwrap();
+            assert_eq!(edges, vec![(ch1, 2), (ch2, 1)]);
+            let bobs = service
+                .get_channel_activity_of(
+                    guild,
+                    |user| user == bob,
+                    None::<DateTime<Utc>>,
+                    None::<DateTime<Utc>>,
+                )
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(channel, activity)| (channel, activity.total))
+                .collect::<Vec<_>>();
+            assert_eq!(bobs, vec![(ch1, 1), (ch2, 1)]);
+            let users = service
+                .get_user_stats(guild, Some(&[ch1]), Some(at(5, 0)), None::<DateTime<Utc>>)
+                .await
+                .unwrap();
+            assert_eq!(users, vec![(alice, 2), (bob, 1)]);
+            let page = async |by, channels: Option<&[ChannelId]>, from, offset| {
+                service
+                    .get_activity_page(by, guild, channels, from, None::<DateTime<Utc>>, offset, 1)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|(id, activity)| (id, activity.total))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                page(ActivityBy::Channel, None, None, 0).await,
+                vec![(10, 3)]
+            );
+            assert_eq!(
+                page(ActivityBy::Channel, None, None, 1).await,
+                vec![(20, 2)]
+            );
+            assert!(page(ActivityBy::Channel, None, None, 2).await.is_empty());
+            let ch1_only = Some(&[ch1][..]);
+            assert_eq!(
+                page(ActivityBy::User, ch1_only, Some(at(5, 0)), 1).await,
+                vec![(200, 1)]
+            );
+            assert_eq!(
+                service
+                    .get_activity_sum(guild, ch1_only, Some(at(5, 0)), None::<DateTime<Utc>>)
+                    .await
+                    .unwrap()
+                    .total,
+                3
+            );
+            let alice_channels = service
+                .get_user_channel_activity(
+                    guild,
+                    alice,
+                    None::<DateTime<Utc>>,
+                    None::<DateTime<Utc>>,
+                )
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(channel, activity)| (channel, activity.total))
+                .collect::<Vec<_>>();
+            assert_eq!(alice_channels, vec![(ch1, 2), (ch2, 1)]);
+            assert_eq!(
+                service.first_seen(guild, bob).await.unwrap(),
+                Some(at(3, 12))
+            );
+            let active = service
+                .get_active_users(guild, Some(&[ch2]), Some(at(4, 0)), None::<DateTime<Utc>>)
+                .await
+                .unwrap();
+            assert_eq!(active, HashSet::from([alice, bob]));
+            let active = service
+                .get_active_users(guild, None, Some(at(5, 12)), Some(at(1, 6)))
+                .await
+                .unwrap();
+            assert_eq!(active, HashSet::from([alice, bob]));
+            let daily = service
+                .get_daily_active_users(guild, None, Some(at(5, 12)), None::<DateTime<Utc>>)
+                .await
+                .unwrap();
+            assert_eq!(
+                daily,
+                vec![
+                    (at(5, 0).date_naive(), 1),
+                    (at(3, 0).date_naive(), 1),
+                    (at(1, 0).date_naive(), 1),
+                    (at(0, 0).date_naive(), 1),
+                ]
+            );
+            let first_seen = service
+                .first_seen_by_user(guild, Some(&[ch2]))
+                .await
+                .unwrap();
+            assert_eq!(
+                first_seen,
+                HashMap::from([(alice, at(0, 0)), (bob, at(3, 12))])
+            );
+        };
+
+        // The migration rolled up every day before today, late records are counted into them
+        assert_eq!(
+            db.rollup().watermark().await.unwrap(),
+            Some(at(1, 0).date_naive())
+        );
+        check().await;
+
+        // Rebuilding the rollups from scratch gives the same numbers
+        db.rollup().clear().await.unwrap();
+        assert_eq!(db.rollup().roll_up(at(1, 0).date_naive()).await.unwrap(), 5);
+        check().await;
+    }
+
+    #[tokio::test]
+    async fn test_prune() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let service = db.message();
+        let now = Utc::now();
+        for i in 0..25 {
+            let time = now - chrono::Duration::days(i);
+            service
+                .record(
+                    MessageId::new(i as u64 + 1),
+                    UserId::new(1),
+                    GuildId::new(1),
+                    ChannelId::new(1),
+                    time.into(),
+                )
+                .await
+                .unwrap();
+        }
+        let cutoff = now - chrono::Duration::days(10);
+        assert_eq!(service.prune(cutoff, 4).await.unwrap(), 14);
+        assert_eq!(service.prune(cutoff, 4).await.unwrap(), 0);
+        // Pruned days are still counted through their rollups
+        let stats = service
+            .get_channel_stats(
+                GuildId::new(1),
+                None::<DateTime<Utc>>,
+                None::<DateTime<Utc>>,
+            )
+            .await
+            .unwrap();
+        assert_eq!(stats, vec![(ChannelId::new(1), 25)]);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_messages() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let service = db.message();
+        let (guild, channel, user) = (GuildId::new(1), ChannelId::new(1), UserId::new(1));
+        let now = Utc::now();
+        // Message 1 falls on a rolled up day, messages 2 and 3 on today
+        for (id, time) in [(1, now - chrono::Duration::days(2)), (2, now), (3, now)] {
+            service
+                .record(MessageId::new(id), user, guild, channel, time.into())
+                .await
+                .unwrap();
+        }
+        let ids = [MessageId::new(1), MessageId::new(2), MessageId::new(404)];
+        assert_eq!(service.mark_deleted(&ids, now).await.unwrap(), 2);
+        // Deleting twice does not count twice
+        assert_eq!(service.mark_deleted(&ids, now).await.unwrap(), 0);
+        service.mark_edited(MessageId::new(3)).await.unwrap();
+        let edited = Entity::find_by_id(3)
+            .one(db.inner())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(edited.edited_count, 1);
+
+        let expected = Activity {
+            total: 3,
+            deleted: 2,
+        };
+        let check = async || {
+            let channels = service
+                .get_channel_activity(guild, None::<DateTime<Utc>>, None::<DateTime<Utc>>)
+                .await
+                .unwrap();
+            assert_eq!(channels, vec![(channel, expected)]);
+            let users = service
+                .get_user_activity(guild, None, None::<DateTime<Utc>>, None::<DateTime<Utc>>)
+                .await
+                .unwrap();
+            assert_eq!(users, vec![(user, expected)]);
+        };
+        check().await;
+        db.rollup().clear().await.unwrap();
+        db.rollup()
+            .roll_up((now - chrono::Duration::days(1)).date_naive())
+            .await
+            .unwrap();
+        check().await;
+        assert_eq!(expected.kept(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hourly_activity() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let service = db.message();
+        let (guild, channel) = (GuildId::new(1), ChannelId::new(1));
+        let (alice, bob) = (UserId::new(1), UserId::new(2));
+        let today = day_start(Utc::now().date_naive());
+        let at = |days: i64, minutes: i64| {
+            today - chrono::Duration::days(days) + chrono::Duration::minutes(minutes)
+        };
+        let messages = [
+            (at(3, 600), alice),
+            (at(1, 300), alice),
+            (at(0, 30), alice),
+            (at(0, 50), bob),
+            (at(0, 70), alice),
+        ];
+        for (i, (time, user)) in messages.into_iter().enumerate() {
+            service
+                .record(
+                    MessageId::new(i as u64 + 1),
+                    user,
+                    guild,
+                    channel,
+                    time.into(),
+                )
+                .await
+                .unwrap();
+        }
+        let hourly = async |user: Option<UserId>| {
+            service
+                .get_hourly_activity(guild, Some(&[channel]), user, None, None)
+                .await
+                .unwrap()
+                .counts
+        };
+        // The oldest raw day is served by its rollup, a single message lands in its last hour
+        assert_eq!(
+            hourly(None).await,
+            vec![
+                (at(3, 23 * 60), 1),
+                (at(1, 300), 1),
+                (at(0, 0), 2),
+                (at(0, 60), 1)
+            ]
+        );
+        // Pruned days are served by their rollups
+        service.prune(today, 100).await.unwrap();
+        let alice_hourly = hourly(Some(alice)).await;
+        assert_eq!(
+            alice_hourly,
+            vec![
+                (at(3, 23 * 60), 1),
+                (at(1, 23 * 60), 1),
+                (at(0, 0), 1),
+                (at(0, 60), 1)
+            ]
+        );
+        let since_yesterday = service
+            .get_hourly_activity(guild, None, None, Some(at(1, 0)), Some(at(0, 60)))
+            .await
+            .unwrap();
+        assert_eq!(
+            since_yesterday.counts,
+            vec![(at(1, 23 * 60), 1), (at(0, 0), 2)]
+        );
+        assert_eq!(since_yesterday.spread_until, Some(at(0, 0)));
+
+        let spread = spread_day(today.date_naive(), 30).collect::<Vec<_>>();
+        assert_eq!(spread.len(), 24);
+        assert_eq!(spread.iter().map(|(_, count)| count).sum::<u64>(), 30);
+    }
 }
@@ -1,2 +1,13 @@
+mod backfill;
+mod cookie_ledger;
+mod cookie_outbox;
+mod digest;
 mod flush;
+mod known;
+mod leaked_cookies;
 mod messages;
+mod rollups;
+
+pub(crate) use cookie_outbox::QueuedCookie;
+pub(crate) use messages::{Activity, ActivityBy, HourlyActivity};
+pub(crate) use rollups::day_start;
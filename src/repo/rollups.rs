@@ -0,0 +1,243 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use entities::{daily_channel_activity, daily_user_activity, messages, rollup_state};
+use sea_orm::{
+    ConnectionTrait, DbBackend, QueryOrder, Set, Statement, TransactionTrait, prelude::*,
+    sea_query::OnConflict,
+};
+use serenity::all::*;
+
+use crate::{database::BotDatabase, error::BotError};
+
+/// Name of the `rollup_state` row tracking the message rollups.
+const MESSAGES: &str = "messages";
+
+pub struct RollupRepo<'a>(&'a BotDatabase);
+impl BotDatabase {
+    /// Get a reference to the daily activity rollups
+    pub fn rollup(&self) -> RollupRepo<'_> {
+        RollupRepo(self)
+    }
+}
+
+/// Start of a UTC day.
+pub fn day_start(day: NaiveDate) -> DateTime<Utc> {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+impl RollupRepo<'_> {
+    /// Last day whose rollups are complete, `None` before the first roll up
+    pub async fn watermark(&self) -> Result<Option<NaiveDate>, BotError> {
+        self.watermark_in(self.0.inner()).await
+    }
+
+    /// The watermark as seen by `db`, a transaction reads it alongside its own writes
+    pub(super) async fn watermark_in(
+        &self,
+        db: &impl ConnectionTrait,
+    ) -> Result<Option<NaiveDate>, BotError> {
+        Ok(rollup_state::Entity::find_by_id(MESSAGES)
+            .one(db)
+            .await?
+            .map(|state| state.rolled_up_to))
+    }
+
+    /// Count a message recorded after its day was already rolled up
+    pub async fn bump(
+        &self,
+        db: &impl ConnectionTrait,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        user_id: UserId,
+        day: NaiveDate,
+    ) -> Result<(), BotError> {
+        let channel = daily_channel_activity::ActiveModel {
+            guild_id: Set(guild_id.get() as i64),
+            day: Set(day),
+            channel_id: Set(channel_id.get() as i64),
+            message_count: Set(1),
+            deleted_count: Set(0),
+        };
+        daily_channel_activity::Entity::insert(channel)
+            .on_conflict(
+                OnConflict::columns([
+                    daily_channel_activity::Column::GuildId,
+                    daily_channel_activity::Column::Day,
+                    daily_channel_activity::Column::ChannelId,
+                ])
+                .value(
+                    daily_channel_activity::Column::MessageCount,
+                    Expr::col(daily_channel_activity::Column::MessageCount).add(1),
+                )
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        let user = daily_user_activity::ActiveModel {
+            guild_id: Set(guild_id.get() as i64),
+            day: Set(day),
+            user_id: Set(user_id.get() as i64),
+            channel_id: Set(channel_id.get() as i64),
+            message_count: Set(1),
+            deleted_count: Set(0),
+        };
+        daily_user_activity::Entity::insert(user)
+            .on_conflict(
+                OnConflict::columns([
+                    daily_user_activity::Column::GuildId,
+                    daily_user_activity::Column::Day,
+                    daily_user_activity::Column::UserId,
+                    daily_user_activity::Column::ChannelId,
+                ])
+                .value(
+                    daily_user_activity::Column::MessageCount,
+                    Expr::col(daily_user_activity::Column::MessageCount).add(1),
+                )
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Count the deletion of a message whose day was already rolled up
+    pub async fn bump_deleted(
+        &self,
+        db: &impl ConnectionTrait,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        user_id: UserId,
+        day: NaiveDate,
+    ) -> Result<(), BotError> {
+        daily_channel_activity::Entity::update_many()
+            .col_expr(
+                daily_channel_activity::Column::DeletedCount,
+                Expr::col(daily_channel_activity::Column::DeletedCount).add(1),
+            )
+            .filter(daily_channel_activity::Column::GuildId.eq(guild_id.get() as i64))
+            .filter(daily_channel_activity::Column::Day.eq(day))
+            .filter(daily_channel_activity::Column::ChannelId.eq(channel_id.get() as i64))
+            .exec(db)
+            .await?;
+        daily_user_activity::Entity::update_many()
+            .col_expr(
+                daily_user_activity::Column::DeletedCount,
+                Expr::col(daily_user_activity::Column::DeletedCount).add(1),
+            )
+            .filter(daily_user_activity::Column::GuildId.eq(guild_id.get() as i64))
+            .filter(daily_user_activity::Column::Day.eq(day))
+            .filter(daily_user_activity::Column::UserId.eq(user_id.get() as i64))
+            .filter(daily_user_activity::Column::ChannelId.eq(channel_id.get() as i64))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Drop every rollup and the watermark
+    pub async fn clear(&self) -> Result<(), BotError> {
+        daily_channel_activity::Entity::delete_many()
+            .exec(self.0.inner())
+            .await?;
+        daily_user_activity::Entity::delete_many()
+            .exec(self.0.inner())
+            .await?;
+        rollup_state::Entity::delete_many()
+            .exec(self.0.inner())
+            .await?;
+        Ok(())
+    }
+
+    /// Roll up every day after the watermark up to and including `until`, returns the number of
+    /// days rolled up
+    pub async fn roll_up(&self, until: NaiveDate) -> Result<u64, BotError> {
+        let start = match self.watermark().await? {
+            Some(watermark) => watermark.succ_opt(),
+            None => {
+                // Claim the watermark before looking for messages, one recorded meanwhile is then
+                // either found here or bumped into a rollup
+                let txn = self.0.inner().begin().await?;
+                self.set_watermark(&txn, until).await?;
+                let first = messages::Entity::find()
+                    .order_by_asc(messages::Column::Timestamp)
+                    .one(&txn)
+                    .await?;
+                let Some(first) = first else {
+                    // Nothing recorded yet, every day up to `until` is trivially complete
+                    txn.commit().await?;
+                    return Ok(0);
+                };
+                txn.rollback().await?;
+                Some(first.timestamp().date_naive())
+            }
+        };
+        let Some(mut day) = start else {
+            return Ok(0);
+        };
+        let mut days = 0;
+        while day <= until {
+            self.roll_up_day(day).await?;
+            days += 1;
+            day = day.succ_opt().expect("date overflow");
+        }
+        Ok(days)
+    }
+
+    /// Rebuild the rollups of a single day from the raw messages and advance the watermark to it,
+    /// in a transaction that writes first so recording and deleting messages wait for it
+    async fn roll_up_day(&self, day: NaiveDate) -> Result<(), BotError> {
+        let start = DateTimeWithTimeZone::from(day_start(day));
+        let end = DateTimeWithTimeZone::from(day_start(day.succ_opt().expect("date overflow")));
+        let txn = self.0.inner().begin().await?;
+        daily_channel_activity::Entity::delete_many()
+            .filter(daily_channel_activity::Column::Day.eq(day))
+            .exec(&txn)
+            .await?;
+        daily_user_activity::Entity::delete_many()
+            .filter(daily_user_activity::Column::Day.eq(day))
+            .exec(&txn)
+            .await?;
+        txn.execute(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            "INSERT INTO daily_channel_activity
+                 (guild_id, day, channel_id, message_count, deleted_count)
+             SELECT guild_id, ?, channel_id, COUNT(*), SUM(deleted_at IS NOT NULL) FROM messages
+             WHERE timestamp >= ? AND timestamp < ?
+             GROUP BY guild_id, channel_id",
+            [day.into(), start.into(), end.into()],
+        ))
+        .await?;
+        txn.execute(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            "INSERT INTO daily_user_activity
+                 (guild_id, day, user_id, channel_id, message_count, deleted_count)
+             SELECT guild_id, ?, user_id, channel_id, COUNT(*), SUM(deleted_at IS NOT NULL)
+             FROM messages
+             WHERE timestamp >= ? AND timestamp < ?
+             GROUP BY guild_id, user_id, channel_id",
+            [day.into(), start.into(), end.into()],
+        ))
+        .await?;
+        self.set_watermark(&txn, day).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn set_watermark(
+        &self,
+        db: &impl ConnectionTrait,
+        day: NaiveDate,
+    ) -> Result<(), BotError> {
+        let state = rollup_state::ActiveModel {
+            name: Set(MESSAGES.to_owned()),
+            rolled_up_to: Set(day),
+        };
+        rollup_state::Entity::insert(state)
+            .on_conflict(
+                OnConflict::column(rollup_state::Column::Name)
+                    .update_column(rollup_state::Column::RolledUpTo)
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        Ok(())
+    }
+}
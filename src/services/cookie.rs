@@ -0,0 +1,296 @@
+use chrono::{Duration, Utc};
+use reqwest::StatusCode;
+use serenity::all::{CreateEmbed, GuildId, Http, UserId};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::{
+    clewdr::{ClewdrClient, ClewdrError},
+    config::BotCfg,
+    database::BotDatabase,
+    error::BotError,
+    repo::QueuedCookie,
+    utils::{CookieStatus, extract_cookies, format_cookie_results, mask_secret},
+};
+
+const MAX_RESULT_ROWS: usize = 60;
+const RETRY_BASE_DELAY: Duration = Duration::minutes(1);
+const RETRY_MAX_DELAY: Duration = Duration::hours(6);
+const BATCH_SIZE: u64 = 20;
+const MAX_ATTEMPTS: i32 = 12;
+
+/// Held while the outbox is being retried, so the background loop and a manual flush never
+/// submit the same queued cookie twice.
+static OUTBOX_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Classify the outcome of a [`ClewdrClient::submit_cookie`] call.
+pub fn submission_status(result: Result<(), ClewdrError>) -> CookieStatus {
+    match result {
+        Ok(()) => CookieStatus::Accepted,
+        Err(e) => match e.status() {
+            Some(StatusCode::CONFLICT) => CookieStatus::Duplicate,
+            Some(StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY) => {
+                CookieStatus::Invalid
+            }
+            Some(status) => CookieStatus::ServerError(status.to_string()),
+            None => match e {
+                ClewdrError::Unreachable { source } if source.is_timeout() => {
+                    CookieStatus::ServerError("timeout".into())
+                }
+                ClewdrError::Unreachable { .. } => CookieStatus::ServerError("unreachable".into()),
+                e => CookieStatus::ServerError(e.to_string()),
+            },
+        },
+    }
+}
+
+/// Delay before the next attempt of a submission that already failed `attempts` times.
+pub fn retry_backoff(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+    (RETRY_BASE_DELAY * 2i32.pow(exponent)).min(RETRY_MAX_DELAY)
+}
+
+/// Extract the cookies in `text` and submit the ones that pass local validation, one by one.
+///
+/// Cookies that clewdr did not accept or reject outright are queued in the outbox on behalf of
+/// `user_id`, every answer of clewdr is recorded in the contribution ledger.
+pub async fn submit_cookies(
+    db: &BotDatabase,
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    clewdr: &ClewdrClient,
+    text: &str,
+) -> Vec<(String, CookieStatus)> {
+    let mut results = Vec::new();
+    for (cookie, status) in extract_cookies(text) {
+        // Cookies turned down locally never reached clewdr, they are not contributions
+        let status = match status {
+            Some(status) => status,
+            None => {
+                let status = match clewdr.submit_cookie(&cookie).await {
+                    Err(e) if !e.is_rejection() => {
+                        let next = Utc::now() + retry_backoff(1);
+                        match db
+                            .cookie_outbox()
+                            .enqueue(user_id, guild_id, &cookie, e.to_string(), next)
+                            .await
+                        {
+                            Ok(_) => CookieStatus::Queued,
+                            Err(queue_err) => {
+                                warn!("Failed to queue cookie for retry: {queue_err}");
+                                submission_status(Err(e))
+                            }
+                        }
+                    }
+                    result => submission_status(result),
+                };
+                if status != CookieStatus::Queued
+                    && let Err(e) = db
+                        .cookie_ledger()
+                        .record(user_id, guild_id, &cookie, &status)
+                        .await
+                {
+                    warn!("Failed to record cookie submission: {e}");
+                }
+                status
+            }
+        };
+        results.push((cookie, status));
+    }
+    results
+}
+
+/// Summary embed of a batch submission.
+pub fn cookie_results_embed(results: &[(String, CookieStatus)]) -> CreateEmbed {
+    let count = |f: fn(&CookieStatus) -> bool| results.iter().filter(|(_, s)| f(s)).count();
+    let accepted = count(|s| *s == CookieStatus::Accepted);
+    CreateEmbed::new()
+        .title("Cookie submission")
+        .description(format_cookie_results(results, MAX_RESULT_ROWS))
+        .field("Accepted", accepted.to_string(), true)
+        .field(
+            "Duplicate",
+            count(|s| *s == CookieStatus::Duplicate).to_string(),
+            true,
+        )
+        .field(
+            "Invalid",
+            count(|s| *s == CookieStatus::Invalid).to_string(),
+            true,
+        )
+        .field(
+            "Server error",
+            count(|s| matches!(s, CookieStatus::ServerError(_))).to_string(),
+            true,
+        )
+        .field(
+            "Queued",
+            count(|s| *s == CookieStatus::Queued).to_string(),
+            true,
+        )
+        .color(if accepted == results.len() {
+            0x00FF00
+        } else if accepted > 0 {
+            0xFFFF00
+        } else {
+            0xFF0000
+        })
+}
+
+/// What happened to a queued cookie after another attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum OutboxOutcome {
+    /// The cookie landed or was rejected for good, it has left the queue.
+    Settled(CookieStatus),
+    /// Still failing, it stays queued for another attempt.
+    Retrying,
+}
+
+/// Retry every due cookie once, returning the outcome of each.
+pub(crate) async fn retry_due(
+    db: &BotDatabase,
+    clewdr: &ClewdrClient,
+) -> Result<Vec<(QueuedCookie, OutboxOutcome)>, BotError> {
+    let _guard = OUTBOX_LOCK.lock().await;
+    let mut outcomes = Vec::new();
+    for queued in db.cookie_outbox().due(Utc::now(), BATCH_SIZE).await? {
+        let outcome = match clewdr.submit_cookie(&queued.cookie).await {
+            Err(e) if !e.is_rejection() && queued.attempts + 1 < MAX_ATTEMPTS => {
+                let next = Utc::now() + retry_backoff(queued.attempts + 1);
+                db.cookie_outbox()
+                    .retry_later(queued.to_owned(), e.to_string(), next)
+                    .await?;
+                OutboxOutcome::Retrying
+            }
+            result => {
+                let status = submission_status(result);
+                if let Err(e) = db.cookie_outbox().settle(&queued, &status).await {
+                    // Still queued, the next attempt settles it again
+                    warn!("Failed to settle queued cookie: {e}");
+                    continue;
+                }
+                OutboxOutcome::Settled(status)
+            }
+        };
+        outcomes.push((queued, outcome));
+    }
+    Ok(outcomes)
+}
+
+/// Retry the due cookies and DM the submitters of those that settled, returns how many were tried.
+pub async fn process_cookie_outbox(
+    http: &Http,
+    db: &BotDatabase,
+    cfg: &BotCfg,
+) -> Result<usize, BotError> {
+    let Some(clewdr) = ClewdrClient::from_cfg(cfg) else {
+        return Ok(0);
+    };
+    let outcomes = retry_due(db, &clewdr).await?;
+    for (queued, outcome) in &outcomes {
+        let OutboxOutcome::Settled(status) = outcome else {
+            continue;
+        };
+        let masked = mask_secret(&queued.cookie);
+        let content = if *status == CookieStatus::Accepted {
+            info!(
+                "Queued cookie {masked} landed after {} attempts",
+                queued.attempts + 1
+            );
+            format!("✅ 您之前排队的曲奇 `{masked}` 已成功提交给公益站。")
+        } else {
+            warn!("Queued cookie {masked} failed permanently: {status}");
+            format!("❌ 您之前排队的曲奇 `{masked}` 最终提交失败: {status}")
+        };
+        let dm = async || -> Result<(), BotError> {
+            queued
+                .user_id()
+                .create_dm_channel(http)
+                .await?
+                .say(http, content)
+                .await?;
+            Ok(())
+        };
+        if let Err(e) = dm().await {
+            warn!(
+                "Failed to notify {} about queued cookie: {e}",
+                queued.user_id()
+            );
+        }
+    }
+    Ok(outcomes.len())
+}
+
+#[cfg(test)]
+mod test {
+    use migration::{Migrator, MigratorTrait};
+
+    use super::*;
+    use crate::clewdr::stub;
+
+    #[tokio::test]
+    async fn test_retry_due() {
+        let db = BotDatabase::new_memory().await.unwrap();
+        Migrator::up(db.inner(), None).await.unwrap();
+        let outbox = db.cookie_outbox();
+        let now = Utc::now();
+        for (i, cookie) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            let due = now - chrono::Duration::seconds(10 - i as i64);
+            assert!(
+                outbox
+                    .enqueue(UserId::new(1), None, cookie, "down".into(), due)
+                    .await
+                    .unwrap()
+            );
+        }
+        assert!(
+            !outbox
+                .enqueue(UserId::new(1), None, "a", "down".into(), now)
+                .await
+                .unwrap()
+        );
+
+        let (endpoint, _) = stub::serve(vec![(200, ""), (503, ""), (401, ""), (409, "")]);
+        let outcomes = retry_due(&db, &ClewdrClient::new(endpoint, "secret"))
+            .await
+            .unwrap();
+        let outcomes = outcomes
+            .into_iter()
+            .map(|(queued, outcome)| (queued.cookie, outcome))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("a".into(), OutboxOutcome::Settled(CookieStatus::Accepted)),
+                ("b".into(), OutboxOutcome::Retrying),
+                // A misconfigured endpoint is not the cookie's fault
+                ("c".into(), OutboxOutcome::Retrying),
+                ("d".into(), OutboxOutcome::Settled(CookieStatus::Duplicate)),
+            ]
+        );
+
+        let remaining = outbox.next().await.unwrap().unwrap();
+        assert_eq!(outbox.count().await.unwrap(), 2);
+        assert_eq!(remaining.cookie, "b");
+        assert_eq!(remaining.attempts, 2);
+        assert!(remaining.next_attempt_at() > now);
+        assert!(outbox.due(Utc::now(), 10).await.unwrap().is_empty());
+        assert_eq!(
+            db.cookie_ledger()
+                .history(UserId::new(1), 10)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // A flush racing the background loop submits every cookie once
+        outbox.make_all_due(Utc::now()).await.unwrap();
+        let (endpoint, requests) = stub::serve(vec![(200, ""), (200, "")]);
+        let clewdr = ClewdrClient::new(endpoint, "secret");
+        let (first, second) = tokio::join!(retry_due(&db, &clewdr), retry_due(&db, &clewdr));
+        assert_eq!(first.unwrap().len() + second.unwrap().len(), 2);
+        assert_eq!(requests.iter().count(), 2);
+        assert_eq!(outbox.count().await.unwrap(), 0);
+    }
+}
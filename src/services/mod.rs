@@ -0,0 +1,5 @@
+mod cookie;
+mod texts;
+
+pub use cookie::*;
+pub use texts::*;
@@ -0,0 +1,92 @@
+use serde::Deserialize;
+use serenity::all::*;
+use tracing::warn;
+
+const MAX_ATTACHMENT_SIZE: u32 = 64 * 1024;
+
+/// Texts of a message other than its content that may leak a secret: embeds, small text
+/// attachments and the snapshots of a forwarded message.
+pub async fn collect_extra_texts(http: &Http, msg: &Message) -> Vec<String> {
+    let mut texts = msg.embeds.iter().flat_map(embed_texts).collect::<Vec<_>>();
+    let mut attachments = msg.attachments.to_owned();
+    if msg
+        .message_reference
+        .as_ref()
+        .is_some_and(|r| r.kind == MessageReferenceKind::Forward)
+    {
+        // serenity does not model snapshots yet, fetch them from the raw message
+        match http
+            .fire::<ForwardedMessage>(Request::new(
+                Route::ChannelMessage {
+                    channel_id: msg.channel_id,
+                    message_id: msg.id,
+                },
+                LightMethod::Get,
+            ))
+            .await
+        {
+            Ok(forwarded) => {
+                for snapshot in forwarded.message_snapshots {
+                    texts.push(snapshot.message.content);
+                    texts.extend(snapshot.message.embeds.iter().flat_map(embed_texts));
+                    attachments.extend(snapshot.message.attachments);
+                }
+            }
+            Err(e) => warn!(
+                "Failed to fetch snapshots of forwarded message {}: {e}",
+                msg.id
+            ),
+        }
+    }
+    for attachment in attachments.iter().filter(|a| is_small_text(a)) {
+        match attachment.download().await {
+            Ok(bytes) => texts.push(String::from_utf8_lossy(&bytes).into_owned()),
+            Err(e) => warn!("Failed to download attachment {}: {e}", attachment.filename),
+        }
+    }
+    texts
+}
+
+fn embed_texts(embed: &Embed) -> Vec<String> {
+    embed
+        .title
+        .iter()
+        .chain(embed.description.iter())
+        .chain(embed.footer.iter().map(|f| &f.text))
+        .chain(embed.fields.iter().flat_map(|f| [&f.name, &f.value]))
+        .cloned()
+        .collect()
+}
+
+fn is_small_text(attachment: &Attachment) -> bool {
+    const TEXT_EXTENSIONS: [&str; 5] = [".txt", ".json", ".md", ".log", ".csv"];
+    attachment.size <= MAX_ATTACHMENT_SIZE
+        && (attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|t| t.starts_with("text/") || t.starts_with("application/json"))
+            || TEXT_EXTENSIONS
+                .iter()
+                .any(|ext| attachment.filename.to_lowercase().ends_with(ext)))
+}
+
+#[derive(Deserialize)]
+struct ForwardedMessage {
+    #[serde(default)]
+    message_snapshots: Vec<MessageSnapshot>,
+}
+
+#[derive(Deserialize)]
+struct MessageSnapshot {
+    message: SnapshotContent,
+}
+
+#[derive(Deserialize)]
+struct SnapshotContent {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    embeds: Vec<Embed>,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+}
@@ -0,0 +1,139 @@
+use std::{collections::HashSet, fmt::Display, sync::LazyLock};
+
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use super::mask_secret;
+
+/// Anything that starts like a cookie, the strict form is preferred so concatenated cookies split.
+static CANDIDATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"sk-ant-sid01-[A-Za-z0-9_-]{86}-[A-Za-z0-9_-]{6}AA|sk-ant-sid01[A-Za-z0-9_-]*")
+        .expect("Cookie candidate pattern should be valid")
+});
+static VALID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^sk-ant-sid01-[A-Za-z0-9_-]{86}-[A-Za-z0-9_-]{6}AA$")
+        .expect("Cookie pattern should be valid")
+});
+
+/// Outcome of a single cookie in a batch submission.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CookieStatus {
+    Accepted,
+    Duplicate,
+    Invalid,
+    ServerError(String),
+    /// The endpoint is down, the cookie waits in the outbox for another attempt.
+    Queued,
+}
+
+impl Display for CookieStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CookieStatus::Accepted => write!(f, "✅ accepted"),
+            CookieStatus::Duplicate => write!(f, "♻️ duplicate"),
+            CookieStatus::Invalid => write!(f, "❌ invalid"),
+            CookieStatus::ServerError(e) => write!(f, "⚠️ server error ({e})"),
+            CookieStatus::Queued => write!(f, "⏳ queued for retry"),
+        }
+    }
+}
+
+impl CookieStatus {
+    /// Stable identifier of the status, as stored in the contribution ledger.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CookieStatus::Accepted => "accepted",
+            CookieStatus::Duplicate => "duplicate",
+            CookieStatus::Invalid => "invalid",
+            CookieStatus::ServerError(_) => "server_error",
+            CookieStatus::Queued => "queued",
+        }
+    }
+
+    /// Response details worth keeping alongside the status.
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            CookieStatus::ServerError(e) => Some(e.to_owned()),
+            _ => None,
+        }
+    }
+}
+
+/// Hex encoded SHA-256 of a cookie, so that contributions can be told apart without storing them.
+pub fn hash_cookie(cookie: &str) -> String {
+    format!("{:x}", Sha256::digest(cookie.as_bytes()))
+}
+
+/// Extract every cookie-looking token from `text` in order of appearance.
+///
+/// Repeated and malformed tokens are settled locally, tokens that still need to be submitted
+/// have no status yet.
+pub fn extract_cookies(text: &str) -> Vec<(String, Option<CookieStatus>)> {
+    let mut seen = HashSet::new();
+    CANDIDATE
+        .find_iter(text)
+        .map(|m| {
+            let cookie = m.as_str().to_owned();
+            let status = if !seen.insert(cookie.to_owned()) {
+                Some(CookieStatus::Duplicate)
+            } else if !VALID.is_match(&cookie) {
+                Some(CookieStatus::Invalid)
+            } else {
+                None
+            };
+            (cookie, status)
+        })
+        .collect()
+}
+
+/// Render the per-cookie results as a code block, with every cookie masked.
+pub fn format_cookie_results(results: &[(String, CookieStatus)], limit: usize) -> String {
+    let mut rows = results
+        .iter()
+        .take(limit)
+        .enumerate()
+        .map(|(i, (cookie, status))| format!("{:>3}. {:<20} {status}", i + 1, mask_secret(cookie)))
+        .collect::<Vec<_>>();
+    if results.len() > limit {
+        rows.push(format!("… and {} more", results.len() - limit));
+    }
+    format!("```\n{}\n```", rows.join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cookie(c: char) -> String {
+        format!(
+            "sk-ant-sid01-{}-{}AA",
+            c.to_string().repeat(86),
+            c.to_string().repeat(6)
+        )
+    }
+
+    #[test]
+    fn test_extract_cookies() {
+        let (a, b) = (cookie('a'), cookie('b'));
+        let text = format!("sessionKey={a}\n{a}, {b}{a}\nsk-ant-sid01-short");
+        let extracted = extract_cookies(&text);
+        assert_eq!(
+            extracted,
+            vec![
+                (a.to_owned(), None),
+                (a.to_owned(), Some(CookieStatus::Duplicate)),
+                (b, None),
+                (a, Some(CookieStatus::Duplicate)),
+                ("sk-ant-sid01-short".to_owned(), Some(CookieStatus::Invalid)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_masks_cookies() {
+        let a = cookie('a');
+        let formatted = format_cookie_results(&[(a.to_owned(), CookieStatus::Accepted)], 10);
+        assert!(!formatted.contains(&a));
+        assert!(formatted.contains("accepted"));
+    }
+}
@@ -1,3 +1,9 @@
 mod children;
+mod cookie;
+mod secrets;
+mod threads;
 
 pub use children::get_children_channels;
+pub use cookie::*;
+pub use secrets::*;
+pub use threads::*;
@@ -0,0 +1,272 @@
+use std::ops::Range;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+
+/// How the text is rewritten before a rule's pattern is applied.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Normalization {
+    /// Match against the raw text.
+    #[default]
+    None,
+    /// Drop everything except alphanumerics, `-` and `_`, so that secrets split up with
+    /// spaces, zero-width characters or markdown still match.
+    Alphanumeric,
+}
+
+/// What to do with a message that leaks a secret.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum SecretAction {
+    /// Reply with the warning and leave the message alone.
+    #[default]
+    Warn,
+    /// Delete the message and post the warning.
+    Delete,
+    /// Delete the message, repost it with the secret masked and post the warning.
+    Redact,
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretRule {
+    pub name: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pattern: Regex,
+    #[serde(default)]
+    pub normalization: Normalization,
+    #[serde(default)]
+    pub action: SecretAction,
+    /// Warning sent to the author, `{name}` and `{declaration}` are substituted.
+    pub warning: String,
+    /// Phrase that, when present in the message, skips this rule.
+    #[serde(default)]
+    pub declaration: Option<String>,
+    /// Whether the secret is a cookie that can be submitted to the cookie endpoint.
+    #[serde(default)]
+    pub submittable: bool,
+}
+
+/// A secret found by a [`SecretRule`], `range` is the byte range in the original text.
+#[derive(Debug, Clone)]
+pub struct SecretMatch<'a> {
+    pub rule: &'a SecretRule,
+    pub range: Range<usize>,
+    /// The secret as the rule matched it, after normalization.
+    pub secret: String,
+}
+
+const COOKIE_DECLARATION: &str = "我明白公屏发送 Cookie 的风险, Cookie 可能会被滥用，包括用于非 AIRP 用途、使用高消耗模型如 Claude Opus 等。";
+const COOKIE_WARNING: &str = "❌ 我们不建议在公屏发送 Cookie, 这可能会导致 Cookie 被滥用。请谨慎处理您的 Cookie 信息。\n\
+建议使用 `/submit_cookie`(English)或`/提交曲奇`(中文)命令提交 Cookie 给公益站, 以确保安全和隐私。\n\
+如果您确实需要在公屏发送 Cookie, 请确保您已经了解相关风险, 在你的消息中包含以下声明:\n```{declaration}```";
+const KEY_WARNING: &str =
+    "❌ 检测到您的消息中包含 {name}, 该消息已被删除。请立即吊销该密钥并重新生成。";
+
+/// Rules used when the configuration does not provide any.
+pub fn default_secret_rules() -> Vec<SecretRule> {
+    let rule =
+        |name: &str, pattern: &str, normalization, action, warning: &str, declaration| SecretRule {
+            name: name.to_owned(),
+            pattern: Regex::new(pattern).expect("Built-in secret pattern should be valid"),
+            normalization,
+            action,
+            warning: warning.to_owned(),
+            declaration,
+            submittable: false,
+        };
+    vec![
+        SecretRule {
+            submittable: true,
+            ..rule(
+                "Claude Cookie",
+                r"sk-ant-sid01-[A-Za-z0-9_-]{86}-[A-Za-z0-9_-]{6}AA",
+                Normalization::Alphanumeric,
+                SecretAction::Warn,
+                COOKIE_WARNING,
+                Some(COOKIE_DECLARATION.to_owned()),
+            )
+        },
+        rule(
+            "Anthropic API Key",
+            r"sk-ant-api\d{2}-[A-Za-z0-9_-]{32,}",
+            Normalization::None,
+            SecretAction::Delete,
+            KEY_WARNING,
+            None,
+        ),
+        rule(
+            "OpenAI API Key",
+            r"sk-(?:proj|svcacct|admin)-[A-Za-z0-9_-]{32,}|\bsk-[A-Za-z0-9]{48}\b",
+            Normalization::None,
+            SecretAction::Delete,
+            KEY_WARNING,
+            None,
+        ),
+        rule(
+            "GitHub Token",
+            r"\b(?:gh[pousr]_[A-Za-z0-9]{36}|github_pat_[A-Za-z0-9_]{82})\b",
+            Normalization::None,
+            SecretAction::Delete,
+            KEY_WARNING,
+            None,
+        ),
+        rule(
+            "Discord Token",
+            r"\b[MNO][A-Za-z0-9_-]{23,25}\.[A-Za-z0-9_-]{6}\.[A-Za-z0-9_-]{27,38}\b",
+            Normalization::None,
+            SecretAction::Delete,
+            KEY_WARNING,
+            None,
+        ),
+    ]
+}
+
+impl Normalization {
+    /// Returns the normalized text along with the original byte range of every normalized byte.
+    fn apply(self, text: &str) -> (String, Vec<Range<usize>>) {
+        let mut normalized = String::with_capacity(text.len());
+        let mut offsets = Vec::with_capacity(text.len());
+        for (i, c) in text.char_indices() {
+            let keep = match self {
+                Normalization::None => true,
+                Normalization::Alphanumeric => c.is_alphanumeric() || c == '-' || c == '_',
+            };
+            if keep {
+                normalized.push(c);
+                offsets.extend(std::iter::repeat_n(i..i + c.len_utf8(), c.len_utf8()));
+            }
+        }
+        (normalized, offsets)
+    }
+}
+
+impl SecretRule {
+    /// Find every secret of this rule in `text`, unless the text carries the bypass phrase.
+    pub fn find<'a>(&'a self, text: &str) -> Vec<SecretMatch<'a>> {
+        if self
+            .declaration
+            .as_deref()
+            .is_some_and(|d| text.contains(d))
+        {
+            return vec![];
+        }
+        let (normalized, offsets) = self.normalization.apply(text);
+        self.pattern
+            .find_iter(&normalized)
+            .filter(|m| !m.is_empty())
+            .map(|m| SecretMatch {
+                rule: self,
+                range: offsets[m.start()].start..offsets[m.end() - 1].end,
+                secret: m.as_str().to_owned(),
+            })
+            .collect()
+    }
+
+    /// Render the warning template of this rule.
+    pub fn warning(&self) -> String {
+        self.warning.replace("{name}", &self.name).replace(
+            "{declaration}",
+            self.declaration.as_deref().unwrap_or_default(),
+        )
+    }
+}
+
+/// Scan `text` with every rule, returning the matches ordered by position.
+pub fn scan_secrets<'a>(rules: &'a [SecretRule], text: &str) -> Vec<SecretMatch<'a>> {
+    let mut matches = rules
+        .iter()
+        .flat_map(|rule| rule.find(text))
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|m| (m.range.start, m.range.end));
+    matches
+}
+
+/// Mask a single secret, keeping a short prefix so the owner can still recognise it.
+pub fn mask_secret(secret: &str) -> String {
+    let keep = (secret.chars().count() / 4).min(16);
+    let prefix = secret.chars().take(keep).collect::<String>();
+    format!("{prefix}****")
+}
+
+/// Replace every matched secret in `text` with its masked form.
+pub fn redact_secrets(text: &str, matches: &[SecretMatch<'_>]) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for m in matches {
+        if m.range.start < cursor {
+            continue; // overlapping match, already masked
+        }
+        redacted.push_str(&text[cursor..m.range.start]);
+        redacted.push_str(&mask_secret(&text[m.range.clone()]));
+        cursor = m.range.end;
+    }
+    redacted.push_str(&text[cursor..]);
+    redacted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cookie() -> String {
+        format!(
+            "sk-ant-sid01-{}-AbC_-9AA",
+            &"AbCdEfGhIjKlMnOpQrStUvWxYz0123456789_-".repeat(3)[..86]
+        )
+    }
+
+    #[test]
+    fn test_detect_split_cookie() {
+        let rules = default_secret_rules();
+        let cookie = cookie();
+        let text = format!("look: {} {}", &cookie[..10], &cookie[10..]);
+        let matches = scan_secrets(&rules, &text);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].rule.name, "Claude Cookie");
+        assert_eq!(&text[matches[0].range.clone()], &text[6..]);
+        assert_eq!(matches[0].secret, cookie);
+    }
+
+    #[test]
+    fn test_cookie_stops_at_its_end() {
+        let rules = default_secret_rules();
+        let cookie = cookie();
+        for text in [
+            format!("{cookie} thanks everyone"),
+            format!("{cookie}\nthanks everyone"),
+        ] {
+            let matches = scan_secrets(&rules, &text);
+            assert_eq!(matches.len(), 1);
+            assert_eq!(matches[0].secret, cookie);
+            assert_eq!(
+                redact_secrets(&text, &matches),
+                text.replace(&cookie, &mask_secret(&cookie))
+            );
+        }
+    }
+
+    #[test]
+    fn test_declaration_bypasses_rule() {
+        let rules = default_secret_rules();
+        let text = format!("{COOKIE_DECLARATION} {}", cookie());
+        assert!(scan_secrets(&rules, &text).is_empty());
+    }
+
+    #[test]
+    fn test_redact_keeps_surrounding_text() {
+        let rules = default_secret_rules();
+        let token = format!("ghp_{}", "a1B2".repeat(9));
+        let text = format!("我的 token 是 {token}，别用");
+        let matches = scan_secrets(&rules, &text);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].rule.action, SecretAction::Delete);
+        assert_eq!(
+            redact_secrets(&text, &matches),
+            format!("我的 token 是 {}，别用", mask_secret(&token))
+        );
+    }
+}
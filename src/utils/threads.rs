@@ -0,0 +1,59 @@
+use serenity::{
+    all::*,
+    http::{LightMethod, Request, Route},
+};
+
+use crate::error::BotError;
+
+const PAGE_SIZE: u64 = 100;
+
+/// Which archive of a channel to list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Archive {
+    Public,
+    /// Needs the Manage Threads permission, listed as empty without it
+    Private,
+}
+
+/// List the archived threads of `channel_id`, most recently archived first, a page at a time
+/// until the archive runs out or `more` returns false for the page just read.
+pub async fn get_archived_threads(
+    http: &Http,
+    channel_id: ChannelId,
+    archive: Archive,
+    mut more: impl FnMut(&[GuildChannel]) -> bool,
+) -> Result<Vec<GuildChannel>, BotError> {
+    let route = match archive {
+        Archive::Public => Route::ChannelArchivedPublicThreads { channel_id },
+        Archive::Private => Route::ChannelArchivedPrivateThreads { channel_id },
+    };
+    let mut threads = Vec::new();
+    let mut before = None::<Timestamp>;
+    loop {
+        // serenity takes `before` as a number, Discord wants the archive timestamp
+        let mut params = vec![("limit", PAGE_SIZE.to_string())];
+        if let Some(before) = before {
+            params.push(("before", before.to_string()));
+        }
+        let request = Request::new(route, LightMethod::Get).params(Some(params));
+        let page = match http.fire::<ThreadsData>(request).await {
+            Ok(page) => page,
+            Err(serenity::Error::Http(e))
+                if archive == Archive::Private
+                    && e.status_code() == Some(StatusCode::FORBIDDEN) =>
+            {
+                return Ok(threads);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        before = page
+            .threads
+            .last()
+            .and_then(|t| t.thread_metadata?.archive_timestamp);
+        let more = more(&page.threads);
+        threads.extend(page.threads);
+        if !(more && page.has_more && before.is_some()) {
+            return Ok(threads);
+        }
+    }
+}